@@ -1,81 +1,127 @@
+use crate::damage::DamageKind;
+use crate::events::GameEvent;
 use rodio::{Decoder, OutputStream, Sink, Source};
 use std::io::Cursor;
 use std::time::Duration;
 use rodio::source::{SineWave, TakeDuration};
 
-pub struct AudioSystem {
+// Level music tracks, by the names level files use
+pub const MUSIC_TRACKS: [&str; 4] = ["calm", "fortress", "labyrinth", "dark"];
+
+// Seconds between footsteps while walking
+const FOOTSTEP_INTERVAL: f64 = 0.3;
+
+// Output device and the sinks playing on it
+struct AudioOutput {
     _stream: OutputStream,
     music_sink: Sink,
     sfx_sink: Sink,
-    footstep_timer: f64,
-    last_footstep: f64,
+}
+
+pub struct AudioSystem {
+    output: Option<AudioOutput>, // None when running silent, e.g. headless or without a sound device
+    footstep_timer: f64, // Game time left before the next footstep can play
 }
 
 impl AudioSystem {
     pub fn new() -> Self {
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap_or_else(|_| {
+        let output = Self::open_output();
+        if output.is_none() {
             // Fallback if audio fails
-            println!("Warning: Could not initialize audio");
-            OutputStream::try_default().unwrap()
-        });
-        
-        let music_sink = Sink::try_new(&stream_handle).unwrap();
-        let sfx_sink = Sink::try_new(&stream_handle).unwrap();
+            println!("Warning: Could not initialize audio, running without sound");
+        }
         
         Self {
-            _stream,
-            music_sink,
-            sfx_sink,
+            output,
+            footstep_timer: 0.0,
+        }
+    }
+
+    // Never touches the sound device
+    pub fn silent() -> Self {
+        Self {
+            output: None,
             footstep_timer: 0.0,
-            last_footstep: 0.0,
+        }
+    }
+
+    fn open_output() -> Option<AudioOutput> {
+        let (_stream, stream_handle) = OutputStream::try_default().ok()?;
+        let music_sink = Sink::try_new(&stream_handle).ok()?;
+        let sfx_sink = Sink::try_new(&stream_handle).ok()?;
+        Some(AudioOutput { _stream, music_sink, sfx_sink })
+    }
+
+    fn play_music<S: Source<Item = f32> + Send + 'static>(&self, source: S, volume: f32) {
+        if let Some(output) = &self.output {
+            output.music_sink.stop();
+            // The sink volume is the player's music setting, the track level goes on the source
+            output.music_sink.append(source.amplify(volume));
+            output.music_sink.play();
+        }
+    }
+
+    fn play_sfx<S: Source<Item = f32> + Send + 'static>(&self, source: S) {
+        if let Some(output) = &self.output {
+            output.sfx_sink.append(source);
         }
     }
 
     pub fn play_menu_music(&self) {
-        self.music_sink.stop();
-        
         // Generate a simple menu tune
-        let source = self.generate_menu_music();
-        self.music_sink.append(source);
-        self.music_sink.set_volume(0.3);
+        self.play_music(self.generate_menu_music(), 0.3);
     }
 
-    pub fn play_game_music(&self) {
-        self.music_sink.stop();
-        
-        // Generate ambient game music
-        let source = self.generate_game_music();
-        self.music_sink.append(source);
-        self.music_sink.set_volume(0.2);
+    pub fn play_game_music(&self, track: &str) {
+        // Generate ambient game music for the level's track
+        self.play_music(self.generate_game_music(track), 0.2);
+    }
+
+    // Advances the sound timers with the simulation, not the wall clock
+    pub fn update(&mut self, delta_time: f64) {
+        if self.footstep_timer > 0.0 {
+            self.footstep_timer -= delta_time;
+        }
     }
 
     pub fn play_footstep(&mut self) {
-        let current_time = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs_f64();
-        
         // Limit footstep frequency
-        if current_time - self.last_footstep > 0.3 {
-            let source = self.generate_footstep_sound();
-            self.sfx_sink.append(source);
-            self.last_footstep = current_time;
+        if self.footstep_timer <= 0.0 {
+            self.play_sfx(self.generate_footstep_sound());
+            self.footstep_timer = FOOTSTEP_INTERVAL;
         }
     }
 
     pub fn play_success_sound(&self) {
-        let source = self.generate_success_sound();
-        self.sfx_sink.append(source);
+        self.play_sfx(self.generate_success_sound());
     }
 
     pub fn play_death_sound(&self) {
-        let source = self.generate_death_sound();
-        self.sfx_sink.append(source);
+        self.play_sfx(self.generate_death_sound());
+    }
+
+    pub fn play_door_sound(&self) {
+        self.play_sfx(self.generate_door_sound());
+    }
+
+    pub fn play_damage_sound(&self, kind: DamageKind) {
+        self.play_sfx(self.generate_damage_sound(kind));
+    }
+
+    pub fn play_flashlight_sound(&self, on: bool) {
+        self.play_sfx(self.generate_flashlight_sound(on));
     }
 
-    pub fn play_damage_sound(&self) {
-        let source = self.generate_damage_sound();
-        self.sfx_sink.append(source);
+    pub fn handle_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::PlayerDamaged { kind, .. } => self.play_damage_sound(kind),
+            GameEvent::PlayerDied => self.play_death_sound(),
+            GameEvent::LevelCompleted => self.play_success_sound(),
+            GameEvent::DoorOpened { .. } => self.play_door_sound(),
+            GameEvent::FlashlightToggled { on } => self.play_flashlight_sound(on),
+            GameEvent::Footstep => self.play_footstep(),
+            _ => {},
+        }
     }
 
     // Generate procedural audio
@@ -83,8 +129,18 @@ impl AudioSystem {
         SineWave::new(440.0)
     }
 
-    fn generate_game_music(&self) -> SineWave {
-        SineWave::new(220.0)
+    fn generate_game_music(&self, track: &str) -> SineWave {
+        let frequency = match track {
+            "calm" => 220.0,
+            "fortress" => 196.0,
+            "labyrinth" => 247.0,
+            "dark" => 110.0,
+            _ => {
+                println!("Warning: Unknown music track '{}'", track);
+                220.0
+            }
+        };
+        SineWave::new(frequency)
     }
 
     fn generate_footstep_sound(&self) -> TakeDuration<SineWave> {
@@ -99,20 +155,54 @@ impl AudioSystem {
         SineWave::new(110.0).take_duration(Duration::from_secs(2))
     }
 
-    fn generate_damage_sound(&self) -> TakeDuration<SineWave> {
-        SineWave::new(1000.0).take_duration(Duration::from_millis(300))
+    fn generate_door_sound(&self) -> TakeDuration<SineWave> {
+        SineWave::new(150.0).take_duration(Duration::from_millis(400))
+    }
+
+    // A short click, higher when switching on
+    fn generate_flashlight_sound(&self, on: bool) -> TakeDuration<SineWave> {
+        let frequency = if on { 2000.0 } else { 1600.0 };
+        SineWave::new(frequency).take_duration(Duration::from_millis(40))
+    }
+
+    // A dull thud for walls, a hiss for fire, a sharp cry for enemies
+    fn generate_damage_sound(&self, kind: DamageKind) -> TakeDuration<SineWave> {
+        let (frequency, millis) = match kind {
+            DamageKind::Impact => (300.0, 120),
+            DamageKind::Fire => (1400.0, 200),
+            DamageKind::Enemy => (1000.0, 300),
+        };
+        SineWave::new(frequency).take_duration(Duration::from_millis(millis))
     }
 
     pub fn stop_all(&self) {
-        self.music_sink.stop();
-        self.sfx_sink.stop();
+        if let Some(output) = &self.output {
+            output.music_sink.stop();
+            output.sfx_sink.stop();
+        }
+    }
+
+    pub fn pause_music(&self) {
+        if let Some(output) = &self.output {
+            output.music_sink.pause();
+        }
+    }
+
+    pub fn resume_music(&self) {
+        if let Some(output) = &self.output {
+            output.music_sink.play();
+        }
     }
 
     pub fn set_music_volume(&self, volume: f32) {
-        self.music_sink.set_volume(volume);
+        if let Some(output) = &self.output {
+            output.music_sink.set_volume(volume);
+        }
     }
 
     pub fn set_sfx_volume(&self, volume: f32) {
-        self.sfx_sink.set_volume(volume);
+        if let Some(output) = &self.output {
+            output.sfx_sink.set_volume(volume);
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,144 @@
+// Circle-versus-grid collision. Movers are circles that slide along the solid cells they run
+// into instead of stopping, and round smoothly past corners.
+use crate::map::Map;
+
+// A move is split into steps no longer than this fraction of the radius, so nothing tunnels
+// through a wall however fast it goes
+const STEP_FRACTION: f64 = 0.5;
+
+// Push-out passes per step; a circle in a corner touches two walls
+const RESOLVE_PASSES: usize = 4;
+
+// Overlaps smaller than this are rounding noise from the last push, not a new contact
+const CONTACT_EPSILON: f64 = 1e-9;
+
+// Moves a circle by (dx, dy). Returns the new centre and whether a wall took away part of
+// the movement.
+pub fn move_circle(map: &Map, x: f64, y: f64, radius: f64, dx: f64, dy: f64) -> (f64, f64, bool) {
+    let distance = (dx * dx + dy * dy).sqrt();
+    let steps = ((distance / (radius * STEP_FRACTION)).ceil() as usize).max(1);
+    let (mut x, mut y, mut hit) = (x, y, false);
+
+    for _ in 0..steps {
+        x += dx / steps as f64;
+        y += dy / steps as f64;
+        for _ in 0..RESOLVE_PASSES {
+            match push_out(map, x, y, radius) {
+                Some((push_x, push_y)) => {
+                    x += push_x;
+                    y += push_y;
+                    hit = true;
+                }
+                None => break,
+            }
+        }
+    }
+    (x, y, hit)
+}
+
+// Whether a circle at (x, y) overlaps any solid cell
+pub fn circle_blocked(map: &Map, x: f64, y: f64, radius: f64) -> bool {
+    push_out(map, x, y, radius).is_some()
+}
+
+// The push that takes the circle out of the solid cell it overlaps most, None when it is clear
+fn push_out(map: &Map, x: f64, y: f64, radius: f64) -> Option<(f64, f64)> {
+    let mut deepest: Option<(f64, f64, f64)> = None; // Depth and push
+    for cell_y in (y - radius).floor() as i64..=(y + radius).floor() as i64 {
+        for cell_x in (x - radius).floor() as i64..=(x + radius).floor() as i64 {
+            if !is_solid_cell(map, cell_x, cell_y) {
+                continue;
+            }
+            let (left, top) = (cell_x as f64, cell_y as f64);
+            let (right, bottom) = (left + 1.0, top + 1.0);
+
+            // Closest point of the cell to the centre
+            let offset_x = x - x.clamp(left, right);
+            let offset_y = y - y.clamp(top, bottom);
+            let distance = (offset_x * offset_x + offset_y * offset_y).sqrt();
+            let push = if distance > 0.0 {
+                let depth = radius - distance;
+                if depth <= CONTACT_EPSILON {
+                    continue;
+                }
+                (depth, offset_x / distance * depth, offset_y / distance * depth)
+            } else {
+                // Centre inside the cell: leave through the nearest side
+                let exits = [(x - left, -1.0, 0.0), (right - x, 1.0, 0.0), (y - top, 0.0, -1.0), (bottom - y, 0.0, 1.0)];
+                let mut nearest = exits[0];
+                for &exit in &exits[1..] {
+                    if exit.0 < nearest.0 {
+                        nearest = exit;
+                    }
+                }
+                let (gap, dir_x, dir_y) = nearest;
+                (gap + radius, dir_x * (gap + radius), dir_y * (gap + radius))
+            };
+            if deepest.is_none_or(|(depth, _, _)| push.0 > depth) {
+                deepest = Some(push);
+            }
+        }
+    }
+    deepest.map(|(_, push_x, push_y)| (push_x, push_y))
+}
+
+// Cells outside the map are solid
+fn is_solid_cell(map: &Map, x: i64, y: i64) -> bool {
+    x < 0 || y < 0 || map.is_solid(x as usize, y as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::CellType;
+
+    const RADIUS: f64 = 0.2;
+
+    fn map(rows: &[&str]) -> Map {
+        let mut map = Map::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                map.data[y][x] = CellType::from_tile(tile).unwrap();
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn slides_along_a_wall() {
+        let map = map(&["11111", "10001", "10001", "11111"]);
+        let (x, y, hit) = move_circle(&map, 1.5, 1.3, RADIUS, 1.0, -0.5);
+        assert!(hit);
+        assert!((x - 2.5).abs() < 1e-9, "lost movement along the wall: x = {}", x);
+        assert!((y - (1.0 + RADIUS)).abs() < 1e-6, "not resting against the wall: y = {}", y);
+    }
+
+    #[test]
+    fn rounds_an_outside_corner() {
+        // Run along the top of the block at (2, 2), then turn down past its corner
+        let map = map(&["00000", "00000", "00100", "00000", "00000"]);
+        let (x, y, hit) = move_circle(&map, 2.5, 2.0 - RADIUS, RADIUS, 1.0, 1.0);
+        assert!(hit);
+        assert!(!circle_blocked(&map, x, y, RADIUS));
+        assert!(x > 3.0 && y > 2.0, "stopped at the corner instead of rounding it: ({}, {})", x, y);
+    }
+
+    #[test]
+    fn diagonal_into_an_inside_corner_stops_at_both_walls() {
+        let map = map(&["11111", "10001", "10001", "11111"]);
+        let (x, y, hit) = move_circle(&map, 2.0, 2.0, RADIUS, -2.0, -2.0);
+        assert!(hit);
+        assert!((x - (1.0 + RADIUS)).abs() < 1e-6 && (y - (1.0 + RADIUS)).abs() < 1e-6, "ended at ({}, {})", x, y);
+    }
+
+    #[test]
+    fn fast_moves_do_not_tunnel_through_thin_walls() {
+        let map = map(&["1111111", "1010001", "1111111"]);
+        for distance in [1.0, 5.0, 50.0] {
+            let (x, y, hit) = move_circle(&map, 1.5, 1.5, RADIUS, distance, 0.0);
+            assert!(hit);
+            assert!(x <= 2.0 - RADIUS + 1e-9, "went through the wall moving {}: x = {}", distance, x);
+            assert!((y - 1.5).abs() < 1e-9);
+        }
+    }
+}
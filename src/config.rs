@@ -0,0 +1,48 @@
+// Player preference files are plain text, one `name = value` per line.
+// Blank lines and lines starting with # are ignored.
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub struct ConfigEntry {
+    pub line: usize, // 1-based, for warnings
+    pub name: String,
+    pub value: String,
+}
+
+// A missing file reads as empty; malformed lines are reported and skipped
+pub fn read_config(path: &Path) -> io::Result<Vec<ConfigEntry>> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_once('=') {
+            Some((name, value)) => entries.push(ConfigEntry {
+                line: index + 1,
+                name: name.trim().to_string(),
+                value: value.trim().to_string(),
+            }),
+            None => eprintln!("Warning: {}:{}: expected `name = value`", path.display(), index + 1),
+        }
+    }
+    Ok(entries)
+}
+
+pub fn write_config(path: &Path, header: &str, entries: &[(String, String)]) -> io::Result<()> {
+    let mut text = String::new();
+    for line in header.lines() {
+        text.push_str(&format!("# {}\n", line));
+    }
+    for (name, value) in entries {
+        text.push_str(&format!("{} = {}\n", name, value));
+    }
+    fs::write(path, text)
+}
@@ -0,0 +1,273 @@
+// Key bindings for the rebindable actions, the controls file they are saved to and the
+// in-game menu that edits them
+use crate::*;
+use minifb::Key;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+pub const CONTROLS_FILE: &str = "controls.cfg";
+pub const KEYS_PER_ACTION: usize = 2;
+
+// Keys that can be bound. Escape and F11 stay reserved for the menus and the fullscreen toggle.
+const BINDABLE_KEYS: &[Key] = &[
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F12,
+    Key::Up, Key::Down, Key::Left, Key::Right,
+    Key::Space, Key::Enter, Key::Tab, Key::Backspace, Key::Delete, Key::Insert,
+    Key::Home, Key::End, Key::PageUp, Key::PageDown,
+    Key::LeftShift, Key::RightShift, Key::LeftCtrl, Key::RightCtrl, Key::LeftAlt, Key::RightAlt,
+    Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma, Key::Equal, Key::LeftBracket, Key::Minus,
+    Key::Period, Key::RightBracket, Key::Semicolon, Key::Slash,
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+    Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9,
+];
+
+// Name used in the controls file
+pub fn key_name(key: Key) -> String {
+    format!("{:?}", key)
+}
+
+pub fn key_from_name(name: &str) -> Option<Key> {
+    BINDABLE_KEYS.iter().copied().find(|&key| key_name(key).eq_ignore_ascii_case(name))
+}
+
+// Short form for on-screen text
+pub fn key_label(key: Key) -> String {
+    let name = key_name(key);
+    name.strip_prefix("Key").unwrap_or(&name).to_uppercase()
+}
+
+fn default_keys(action: Action) -> &'static [Key] {
+    const LEVEL_KEYS: [Key; LEVEL_SLOTS] = [
+        Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5,
+        Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    ];
+    match action {
+        Action::MoveForward => &[Key::W, Key::Up],
+        Action::MoveBackward => &[Key::S, Key::Down],
+        Action::StrafeLeft => &[Key::A, Key::Left],
+        Action::StrafeRight => &[Key::D, Key::Right],
+        Action::Use => &[Key::E],
+        Action::Flashlight => &[Key::F],
+        Action::Confirm => &[Key::Space],
+        Action::Restart => &[Key::R],
+        Action::Menu => &[Key::M],
+        Action::Endless => &[Key::Key0],
+        Action::Controls => &[Key::C],
+        Action::Options => &[Key::O],
+        Action::SelectLevel(slot) => LEVEL_KEYS.get(slot).map_or(&[], std::slice::from_ref),
+        _ => &[],
+    }
+}
+
+pub fn rebindable_actions() -> impl Iterator<Item = Action> {
+    all_actions().filter(|action| action.group() != ActionGroup::Fixed)
+}
+
+#[derive(Clone)]
+pub struct KeyBindings {
+    keys: HashMap<Action, [Option<Key>; KEYS_PER_ACTION]>,
+}
+
+impl KeyBindings {
+    pub fn defaults() -> Self {
+        let mut keys = HashMap::new();
+        for action in rebindable_actions() {
+            let mut slots = [None; KEYS_PER_ACTION];
+            for (slot, &key) in slots.iter_mut().zip(default_keys(action)) {
+                *slot = Some(key);
+            }
+            keys.insert(action, slots);
+        }
+        Self { keys }
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(Path::new(CONTROLS_FILE))
+    }
+
+    // Actions the file leaves out keep their default keys. Bad lines are reported and skipped,
+    // conflicting keys are reported but kept so the controls menu can show them.
+    pub fn load_from(path: &Path) -> Self {
+        let mut bindings = Self::defaults();
+        let entries = match read_config(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Warning: could not read {}: {}", path.display(), e);
+                return bindings;
+            }
+        };
+
+        for entry in entries {
+            let warn = |message: String| eprintln!("Warning: {}:{}: {}", path.display(), entry.line, message);
+            let action = match Action::from_name(&entry.name).filter(|action| action.group() != ActionGroup::Fixed) {
+                Some(action) => action,
+                None => {
+                    warn(format!("unknown action `{}`", entry.name));
+                    continue;
+                }
+            };
+
+            let mut slots = [None; KEYS_PER_ACTION];
+            let names = entry.value.split(',').map(str::trim).filter(|name| !name.is_empty());
+            for (slot, name) in names.enumerate() {
+                if slot >= KEYS_PER_ACTION {
+                    warn(format!("only {} keys per action are used", KEYS_PER_ACTION));
+                    break;
+                }
+                match key_from_name(name) {
+                    Some(key) => slots[slot] = Some(key),
+                    None => warn(format!("unknown key `{}`", name)),
+                }
+            }
+            bindings.keys.insert(action, slots);
+        }
+
+        for (key, first, second) in bindings.conflicts() {
+            eprintln!("Warning: {}: {} is bound to both {} and {}", path.display(), key_name(key), first.label(), second.label());
+        }
+        bindings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to(Path::new(CONTROLS_FILE))
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        let entries: Vec<(String, String)> = rebindable_actions().map(|action| {
+            let names: Vec<String> = self.keys(action).map(key_name).collect();
+            (action.name(), names.join(", "))
+        }).collect();
+        let header = format!("Key bindings, up to {} keys per action separated by commas.\nEdit them in game from the level select screen.", KEYS_PER_ACTION);
+        write_config(path, &header, &entries)
+    }
+
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = Key> + '_ {
+        self.keys.get(&action).into_iter().flatten().flatten().copied()
+    }
+
+    pub fn key(&self, action: Action, slot: usize) -> Option<Key> {
+        self.keys.get(&action).and_then(|slots| slots.get(slot).copied().flatten())
+    }
+
+    // First key of the action for on-screen hints, "-" when it has none
+    pub fn label(&self, action: Action) -> String {
+        self.keys(action).next().map_or_else(|| String::from("-"), key_label)
+    }
+
+    // Fails with the action that already uses the key in the same group
+    pub fn bind(&mut self, action: Action, slot: usize, key: Key) -> Result<(), Action> {
+        if let Some(other) = self.action_for(key, action.group()).filter(|&other| other != action) {
+            return Err(other);
+        }
+        let slots = self.keys.entry(action).or_insert([None; KEYS_PER_ACTION]);
+        for bound in slots.iter_mut() {
+            if *bound == Some(key) {
+                *bound = None;
+            }
+        }
+        slots[slot] = Some(key);
+        Ok(())
+    }
+
+    pub fn clear(&mut self, action: Action, slot: usize) {
+        if let Some(slots) = self.keys.get_mut(&action) {
+            slots[slot] = None;
+        }
+    }
+
+    // Every key shared by two actions of the same group
+    pub fn conflicts(&self) -> Vec<(Key, Action, Action)> {
+        let actions: Vec<Action> = rebindable_actions().collect();
+        let mut conflicts = Vec::new();
+        for (i, &first) in actions.iter().enumerate() {
+            for &second in actions[i + 1..].iter().filter(|second| second.group() == first.group()) {
+                for key in self.keys(first).filter(|&key| self.keys(second).any(|other| other == key)) {
+                    conflicts.push((key, first, second));
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub fn has_conflict(&self, action: Action) -> bool {
+        self.conflicts().iter().any(|&(_, first, second)| first == action || second == action)
+    }
+
+    fn action_for(&self, key: Key, group: ActionGroup) -> Option<Action> {
+        rebindable_actions().find(|&action| action.group() == group && self.keys(action).any(|bound| bound == key))
+    }
+}
+
+pub struct ControlsMenu {
+    pub selected: usize, // Row in rebindable_actions()
+    pub slot: usize,     // Column, which of the action's keys
+    pub capturing: bool, // Waiting for the key to bind
+    pub status: Option<(String, bool)>, // Last result and whether it was an error
+}
+
+impl ControlsMenu {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            slot: 0,
+            capturing: false,
+            status: None,
+        }
+    }
+
+    pub fn selected_action(&self) -> Action {
+        rebindable_actions().nth(self.selected).unwrap_or(Action::MoveForward)
+    }
+
+    // Returns false once the player leaves the menu. Changes are saved right away when persist is set.
+    pub fn update(&mut self, input: &InputState, bindings: &mut KeyBindings, persist: bool) -> bool {
+        let action = self.selected_action();
+
+        if self.capturing {
+            if input.is_pressed(Action::Back) {
+                self.capturing = false;
+                self.status = None;
+            } else if let Some(key) = input.keys_pressed.iter().copied().find(|key| BINDABLE_KEYS.contains(key)) {
+                self.capturing = false;
+                match bindings.bind(action, self.slot, key) {
+                    Ok(()) => self.save(bindings, persist, format!("{} bound to {}", action.label(), key_label(key))),
+                    Err(other) => {
+                        let text = format!("{} is already used by {}", key_label(key), other.label());
+                        self.status = Some((text, true));
+                    }
+                }
+            }
+            return true;
+        }
+
+        let rows = rebindable_actions().count();
+        if input.is_pressed(Action::Back) {
+            return false;
+        } else if input.is_pressed(Action::MenuUp) {
+            self.selected = (self.selected + rows - 1) % rows;
+        } else if input.is_pressed(Action::MenuDown) {
+            self.selected = (self.selected + 1) % rows;
+        } else if input.is_pressed(Action::MenuLeft) || input.is_pressed(Action::MenuRight) {
+            self.slot = (self.slot + 1) % KEYS_PER_ACTION;
+        } else if input.is_pressed(Action::MenuSelect) {
+            self.capturing = true;
+            self.status = Some((format!("Press a key for {}, ESC to cancel", action.label()), false));
+        } else if input.is_pressed(Action::Clear) {
+            bindings.clear(action, self.slot);
+            self.save(bindings, persist, format!("Cleared a key of {}", action.label()));
+        }
+        true
+    }
+
+    fn save(&mut self, bindings: &KeyBindings, persist: bool, done: String) {
+        let result = if persist { bindings.save() } else { Ok(()) };
+        self.status = Some(match result {
+            Ok(()) => (done, false),
+            Err(e) => (format!("Could not save {}: {}", CONTROLS_FILE, e), true),
+        });
+    }
+}
@@ -0,0 +1,203 @@
+// Per-level damage rules: what hurts the player, how much and how often. Levels set them in
+// their header, anything left out keeps the defaults below:
+//
+//   wall_damage: 5 0.5 over_time      amount, cooldown in seconds and mode, or `none`
+//   hazard_damage: 10 0.3 on_enter
+//
+// Walls (and closed doors) hurt while the player runs into them, hazard cells while the player
+// stands on them. All damage then goes through Game::apply_damage, which lets armor take its
+// share. After every hit the other sources cannot hurt the player for a moment, while the
+// source that hit keeps its own cooldown.
+
+// Seconds after a hit during which no other source can hurt the player
+pub const INVULNERABILITY_TIME: f64 = 0.1;
+
+// What hurt the player
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DamageKind {
+    Impact, // Running into walls
+    Fire,   // Hazard cells
+    Enemy,
+}
+
+impl DamageKind {
+    // Share of the damage armor takes instead of health, while it lasts
+    pub fn armor_absorption(self) -> f64 {
+        match self {
+            DamageKind::Impact => 0.8,
+            DamageKind::Fire => 0.3,
+            DamageKind::Enemy => 0.6,
+        }
+    }
+
+    // Splits damage between armor and health, returns (health lost, armor lost)
+    pub fn absorb(self, amount: i32, armor: i32) -> (i32, i32) {
+        let armor_lost = ((amount as f64 * self.armor_absorption()).round() as i32).min(armor);
+        (amount - armor_lost, armor_lost)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DamageMode {
+    OverTime, // Again every cooldown for as long as the contact lasts
+    OnEnter,  // Once per contact, the player has to leave and come back to be hurt again
+}
+
+impl DamageMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "over_time" => Some(DamageMode::OverTime),
+            "on_enter" => Some(DamageMode::OnEnter),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DamageRule {
+    pub amount: i32,
+    pub cooldown: f64, // Seconds before the same source can hurt again
+    pub mode: DamageMode,
+}
+
+impl DamageRule {
+    // `amount cooldown [mode]`, the mode defaults to over_time. None for `none`.
+    pub fn parse(text: &str) -> Result<Option<Self>, ()> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words == ["none"] {
+            return Ok(None);
+        }
+        if words.len() < 2 || words.len() > 3 {
+            return Err(());
+        }
+        let amount: i32 = words[0].parse().map_err(|_| ())?;
+        let cooldown: f64 = words[1].parse().map_err(|_| ())?;
+        if amount < 0 || !(cooldown >= 0.0 && cooldown.is_finite()) {
+            return Err(());
+        }
+        let mode = match words.get(2) {
+            Some(name) => DamageMode::from_name(name).ok_or(())?,
+            None => DamageMode::OverTime,
+        };
+        Ok(Some(Self { amount, cooldown, mode }))
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DamageRules {
+    pub wall: Option<DamageRule>,   // None when running into walls is harmless
+    pub hazard: Option<DamageRule>, // None when hazard cells are harmless
+}
+
+impl Default for DamageRules {
+    fn default() -> Self {
+        Self {
+            wall: Some(DamageRule { amount: 5, cooldown: 0.5, mode: DamageMode::OverTime }),
+            hazard: Some(DamageRule { amount: 10, cooldown: 0.3, mode: DamageMode::OverTime }),
+        }
+    }
+}
+
+// Cooldown and contact of one damage source during play
+#[derive(Clone, Copy, Default)]
+pub struct DamageTimer {
+    pub cooldown: f64,
+    pub hurt_in_contact: bool, // Already hurt the player during the current contact
+}
+
+impl DamageTimer {
+    // Advances one tick and returns the damage dealt in it. While the player cannot be hurt the
+    // source waits, so its cooldown starts from the hit that lands.
+    pub fn tick(&mut self, rule: Option<DamageRule>, touching: bool, vulnerable: bool, delta_time: f64) -> i32 {
+        if !touching {
+            self.hurt_in_contact = false;
+        }
+
+        let mut damage = 0;
+        if let Some(rule) = rule {
+            let hurts = match rule.mode {
+                DamageMode::OverTime => touching,
+                DamageMode::OnEnter => touching && !self.hurt_in_contact,
+            };
+            if hurts && vulnerable && self.cooldown <= 0.0 {
+                damage = rule.amount;
+                self.cooldown = rule.cooldown;
+                self.hurt_in_contact = true;
+            }
+        }
+        if self.cooldown > 0.0 {
+            self.cooldown -= delta_time;
+        }
+        damage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Exact in binary, so cooldowns run out on a known tick
+    const STEP: f64 = 0.125;
+
+    fn hits(timer: &mut DamageTimer, rule: DamageRule, contact: &[bool]) -> Vec<i32> {
+        contact.iter().map(|&touching| timer.tick(Some(rule), touching, true, STEP)).collect()
+    }
+
+    #[test]
+    fn parse_none_turns_the_source_off() {
+        assert_eq!(DamageRule::parse("none"), Ok(None));
+    }
+
+    #[test]
+    fn parse_rejects_bad_rules() {
+        for text in ["", "5", "x 0.5", "-5 0.5", "5 -1", "5 soon", "5 0.5 sometimes", "5 0.5 on_enter extra"] {
+            assert_eq!(DamageRule::parse(text), Err(()), "accepted `{}`", text);
+        }
+    }
+
+    #[test]
+    fn parse_reads_both_modes() {
+        let rule = |amount, cooldown, mode| Ok(Some(DamageRule { amount, cooldown, mode }));
+        assert_eq!(DamageRule::parse("5 0.5"), rule(5, 0.5, DamageMode::OverTime));
+        assert_eq!(DamageRule::parse("5 0.5 over_time"), rule(5, 0.5, DamageMode::OverTime));
+        assert_eq!(DamageRule::parse("10 0.3 on_enter"), rule(10, 0.3, DamageMode::OnEnter));
+    }
+
+    #[test]
+    fn over_time_hurts_again_every_cooldown() {
+        let rule = DamageRule { amount: 5, cooldown: 0.5, mode: DamageMode::OverTime };
+        let mut timer = DamageTimer::default();
+        assert_eq!(hits(&mut timer, rule, &[true; 9]), [5, 0, 0, 0, 5, 0, 0, 0, 5]);
+    }
+
+    #[test]
+    fn on_enter_hurts_once_per_contact() {
+        let rule = DamageRule { amount: 10, cooldown: 0.25, mode: DamageMode::OnEnter };
+        let mut timer = DamageTimer::default();
+        let contact = [true, true, true, true, false, true, true];
+        assert_eq!(hits(&mut timer, rule, &contact), [10, 0, 0, 0, 0, 10, 0]);
+    }
+
+    #[test]
+    fn on_enter_waits_out_the_cooldown_after_a_quick_return() {
+        let rule = DamageRule { amount: 10, cooldown: 0.5, mode: DamageMode::OnEnter };
+        let mut timer = DamageTimer::default();
+        let contact = [true, false, true, true, true, true];
+        assert_eq!(hits(&mut timer, rule, &contact), [10, 0, 0, 0, 10, 0]);
+    }
+
+    #[test]
+    fn blocked_hit_waits_instead_of_using_up_the_cooldown() {
+        let rule = DamageRule { amount: 5, cooldown: 0.5, mode: DamageMode::OverTime };
+        let mut timer = DamageTimer::default();
+        let vulnerable = [false, false, true, true];
+        let dealt: Vec<i32> = vulnerable.iter().map(|&vulnerable| timer.tick(Some(rule), true, vulnerable, STEP)).collect();
+        assert_eq!(dealt, [0, 0, 5, 0]);
+    }
+
+    #[test]
+    fn no_rule_never_hurts() {
+        let mut timer = DamageTimer::default();
+        assert_eq!(timer.tick(None, true, true, STEP), 0);
+    }
+}
@@ -0,0 +1,98 @@
+// Difficulty levels, picked on the level select screen. Each one scales the level's damage rules,
+// the starting health, the flashlight and how far the player can see.
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Nightmare,
+}
+
+pub const DIFFICULTIES: [Difficulty; 4] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard, Difficulty::Nightmare];
+
+impl Difficulty {
+    // Name used in the save and score files
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+            Difficulty::Nightmare => "nightmare",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+            Difficulty::Nightmare => "NIGHTMARE",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        DIFFICULTIES.iter().copied().find(|difficulty| difficulty.name() == name)
+    }
+
+    // The next easier (negative steps) or harder one, stopping at the ends
+    pub fn step(self, steps: i32) -> Self {
+        let index = DIFFICULTIES.iter().position(|&difficulty| difficulty == self).unwrap_or(1) as i32;
+        DIFFICULTIES[(index + steps).clamp(0, DIFFICULTIES.len() as i32 - 1) as usize]
+    }
+
+    pub fn starting_health(self) -> i32 {
+        match self {
+            Difficulty::Easy => 150,
+            Difficulty::Normal => 100,
+            Difficulty::Hard => 75,
+            Difficulty::Nightmare => 50,
+        }
+    }
+
+    // Multipliers on damage amounts and on the cooldowns between hits
+    fn damage_scale(self) -> (f64, f64) {
+        match self {
+            Difficulty::Easy => (0.5, 1.5),
+            Difficulty::Normal => (1.0, 1.0),
+            Difficulty::Hard => (1.5, 0.75),
+            Difficulty::Nightmare => (2.0, 0.5),
+        }
+    }
+
+    // Brightness of the flashlight beam, 1.0 as on Normal
+    pub fn flashlight_strength(self) -> f64 {
+        match self {
+            Difficulty::Easy => 1.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 0.75,
+            Difficulty::Nightmare => 0.5,
+        }
+    }
+
+    // Cells the player can see before everything fades into the fog, for RayCaster::max_depth
+    pub fn view_distance(self) -> f64 {
+        match self {
+            Difficulty::Easy => 24.0,
+            Difficulty::Normal => 20.0,
+            Difficulty::Hard => 14.0,
+            Difficulty::Nightmare => 9.0,
+        }
+    }
+
+    // A level's damage rules at this difficulty. A source that hurts never drops to 0 damage.
+    pub fn damage_rules(self, rules: DamageRules) -> DamageRules {
+        let (amount_scale, cooldown_scale) = self.damage_scale();
+        let scale = |rule: Option<DamageRule>| rule.map(|rule| DamageRule {
+            amount: if rule.amount > 0 { ((rule.amount as f64 * amount_scale).round() as i32).max(1) } else { 0 },
+            cooldown: rule.cooldown * cooldown_scale,
+            mode: rule.mode,
+        });
+        DamageRules {
+            wall: scale(rules.wall),
+            hazard: scale(rules.hazard),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::items::*;
+
+const DOOR_OPEN_TIME: f64 = 0.8; // Seconds to slide fully open or closed
+const DOOR_STAY_OPEN: f64 = 3.0; // Seconds a door waits before closing again
+const DOOR_PASSABLE: f64 = 0.9; // Openness at which the player fits through
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+#[derive(Clone)]
+pub struct Door {
+    pub state: DoorState,
+    pub openness: f64, // 0.0 closed, 1.0 fully slid into the wall
+    pub timer: f64,    // Time left before an open door starts closing
+    pub horizontal: bool, // Panel runs along x (walls east and west), otherwise along y
+    pub lock: Option<KeyColor>, // Key needed to open it, cleared once unlocked
+}
+
+impl Door {
+    pub fn new(horizontal: bool) -> Self {
+        Self {
+            state: DoorState::Closed,
+            openness: 0.0,
+            timer: 0.0,
+            horizontal,
+            lock: None,
+        }
+    }
+
+    // Returns true if the door started opening
+    pub fn open(&mut self) -> bool {
+        match self.state {
+            DoorState::Closed | DoorState::Closing => {
+                self.state = DoorState::Opening;
+                true
+            },
+            DoorState::Open => {
+                self.timer = DOOR_STAY_OPEN;
+                false
+            },
+            DoorState::Opening => false,
+        }
+    }
+
+    pub fn is_passable(&self) -> bool {
+        self.openness >= DOOR_PASSABLE
+    }
+
+    // `blocked` keeps the door from closing on whoever stands in the doorway
+    pub fn update(&mut self, delta_time: f64, blocked: bool) {
+        let speed = delta_time / DOOR_OPEN_TIME;
+        match self.state {
+            DoorState::Closed => {},
+            DoorState::Opening => {
+                self.openness += speed;
+                if self.openness >= 1.0 {
+                    self.openness = 1.0;
+                    self.state = DoorState::Open;
+                    self.timer = DOOR_STAY_OPEN;
+                }
+            },
+            DoorState::Open => {
+                if blocked {
+                    self.timer = DOOR_STAY_OPEN;
+                } else {
+                    self.timer -= delta_time;
+                    if self.timer <= 0.0 {
+                        self.state = DoorState::Closing;
+                    }
+                }
+            },
+            DoorState::Closing => {
+                if blocked {
+                    self.state = DoorState::Opening;
+                    return;
+                }
+                self.openness -= speed;
+                if self.openness <= 0.0 {
+                    self.openness = 0.0;
+                    self.state = DoorState::Closed;
+                }
+            },
+        }
+    }
+}
@@ -3,8 +3,11 @@ use rand::Rng;
 
 pub struct Effects {
     pub flashlight_enabled: bool,
+    pub flashlight_strength: f64, // Beam brightness, 1.0 on Normal difficulty
     pub fog_enabled: bool,
+    pub damage_flash_enabled: bool,
     pub damage_effect_timer: f64,
+    pub damage_color: u32, // Tint of the last hit, its color channels scale the flash
     pub anxiety_effect_timer: f64,
     pub screen_shake_intensity: f64,
     pub screen_shake_timer: f64,
@@ -17,8 +20,11 @@ impl Effects {
     pub fn new() -> Self {
         Self {
             flashlight_enabled: false,
+            flashlight_strength: 1.0,
             fog_enabled: true,
+            damage_flash_enabled: true,
             damage_effect_timer: 0.0,
+            damage_color: 0xFF0000,
             anxiety_effect_timer: 0.0,
             screen_shake_intensity: 0.0,
             screen_shake_timer: 0.0,
@@ -50,9 +56,20 @@ impl Effects {
         self.flashlight_enabled = !self.flashlight_enabled;
     }
 
-    pub fn trigger_damage_effect(&mut self) {
+    // Fire flashes orange, everything else red
+    pub fn trigger_damage_effect(&mut self, kind: DamageKind) {
         self.damage_effect_timer = 0.5;
         self.screen_shake_timer = 0.3;
+        self.damage_color = match kind {
+            DamageKind::Fire => 0xFF8000,
+            DamageKind::Impact | DamageKind::Enemy => 0xFF0000,
+        };
+    }
+
+    pub fn handle_event(&mut self, event: &GameEvent) {
+        if let GameEvent::PlayerDamaged { kind, .. } = *event {
+            self.trigger_damage_effect(kind);
+        }
     }
 
     pub fn reset(&mut self) {
@@ -64,19 +81,21 @@ impl Effects {
     }
 
     pub fn apply_effects(&self, buffer: &mut Vec<u32>) {
-        // Apply simple red overlay when taking damage
-        if self.color_distortion > 0.0 {
+        // Apply simple colored overlay when taking damage
+        if self.damage_flash_enabled && self.color_distortion > 0.0 {
             self.apply_simple_damage_tint(buffer);
         }
     }
     
     fn apply_simple_damage_tint(&self, buffer: &mut Vec<u32>) {
-        let intensity = (self.color_distortion * 100.0) as u32;
+        let intensity = self.color_distortion * 100.0;
+        let add = |shift: u32| (intensity * ((self.damage_color >> shift) & 0xFF) as f64 / 255.0) as u32;
+        let (add_r, add_g, add_b) = (add(16), add(8), add(0));
         for pixel in buffer.iter_mut() {
-            let r = ((*pixel >> 16) & 0xFF) + intensity;
-            let g = (*pixel >> 8) & 0xFF;
-            let b = *pixel & 0xFF;
-            *pixel = ((r.min(255)) << 16) | (g << 8) | b;
+            let r = ((*pixel >> 16) & 0xFF) + add_r;
+            let g = ((*pixel >> 8) & 0xFF) + add_g;
+            let b = (*pixel & 0xFF) + add_b;
+            *pixel = ((r.min(255)) << 16) | ((g.min(255)) << 8) | b.min(255);
         }
     }
 
@@ -188,7 +207,7 @@ impl Effects {
         let flashlight_cone = std::f64::consts::PI / 4.0; // 45 degree cone
         if angle_diff.abs() < flashlight_cone {
             let cone_factor = 1.0 - (angle_diff.abs() / flashlight_cone);
-            1.0 + cone_factor * 1.5 // Much brighter in flashlight beam
+            1.0 + cone_factor * 1.5 * self.flashlight_strength // Much brighter in flashlight beam
         } else {
             0.1 // Much darker outside beam
         }
@@ -212,7 +231,7 @@ impl Effects {
                 
                 if distance < max_radius {
                     let intensity = 1.0 - (distance / max_radius);
-                    let brightness = (intensity * 60.0) as u32; // White overlay intensity
+                    let brightness = (intensity * 60.0 * self.flashlight_strength) as u32; // White overlay intensity
                     
                     let pixel_index = y * window_width + x;
                     let current_pixel = buffer[pixel_index];
@@ -0,0 +1,209 @@
+use crate::*;
+
+const SIGHT_RANGE: f64 = 8.0; // Cells an enemy can see along a clear line
+const LOSE_SIGHT_TIME: f64 = 4.0; // Seconds a chase goes on without seeing the player
+const ATTACK_RANGE: f64 = 0.9;
+const ATTACK_DAMAGE: i32 = 10;
+const ATTACK_COOLDOWN: f64 = 1.0;
+const CONTACT_RANGE: f64 = 0.4; // Bumping into an enemy hurts even if it has not noticed you
+const CONTACT_DAMAGE: i32 = 5;
+const CONTACT_COOLDOWN: f64 = 0.5;
+const PATROL_SPEED: f64 = 1.2;
+const CHASE_SPEED: f64 = 2.2;
+const REPATH_INTERVAL: f64 = 0.5; // Seconds between path searches
+const ENEMY_RADIUS: f64 = 0.2; // Collision circle, in cells
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EnemyState {
+    Idle,   // Guards its post until it sees the player
+    Patrol, // Walks its waypoints in a loop
+    Chase,  // Follows the player, or where it was last seen
+    Attack, // Close enough to strike
+}
+
+#[derive(Clone)]
+pub struct Enemy {
+    pub x: f64,
+    pub y: f64,
+    pub state: EnemyState,
+    pub sprite: Sprite,
+    waypoints: Vec<(f64, f64)>, // Patrol route, starting at the spawn point
+    waypoint: usize,
+    last_seen: (f64, f64),
+    path: Vec<(usize, usize)>, // Cells still to walk, next one last
+    path_goal: (usize, usize),
+    repath_timer: f64,
+    unseen_time: f64,
+    attack_timer: f64,
+    contact_timer: f64,
+}
+
+impl Enemy {
+    // With no extra waypoints the enemy guards its spawn point
+    pub fn new(x: f64, y: f64, waypoints: Vec<(f64, f64)>) -> Self {
+        let state = if waypoints.is_empty() { EnemyState::Idle } else { EnemyState::Patrol };
+        let mut route = vec![(x, y)];
+        route.extend(waypoints);
+
+        let mut enemy = Self {
+            x,
+            y,
+            state,
+            sprite: Sprite::new(x, y, SPRITE_GUARD_WALK[0]),
+            waypoints: route,
+            waypoint: 0,
+            last_seen: (x, y),
+            path: Vec::new(),
+            path_goal: (x as usize, y as usize),
+            repath_timer: 0.0,
+            unseen_time: 0.0,
+            attack_timer: 0.0,
+            contact_timer: 0.0,
+        };
+        enemy.set_state(state);
+        enemy
+    }
+
+    // Returns the damage dealt to the player this frame. While the player cannot be hurt the
+    // enemy holds its blow instead of wasting it.
+    pub fn update(&mut self, delta_time: f64, player: &Player, map: &Map, raycaster: &RayCaster, can_hurt: bool) -> i32 {
+        self.repath_timer -= delta_time;
+        self.attack_timer -= delta_time;
+        self.contact_timer -= delta_time;
+
+        let dx = player.x - self.x;
+        let dy = player.y - self.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let sees_player = distance < SIGHT_RANGE && raycaster.has_line_of_sight(self.x, self.y, player.x, player.y, map);
+        if sees_player {
+            self.last_seen = (player.x, player.y);
+            self.unseen_time = 0.0;
+        } else {
+            self.unseen_time += delta_time;
+        }
+
+        // State transitions
+        match self.state {
+            EnemyState::Idle | EnemyState::Patrol => {
+                if sees_player {
+                    self.set_state(EnemyState::Chase);
+                }
+            },
+            EnemyState::Chase => {
+                if sees_player && distance < ATTACK_RANGE {
+                    self.set_state(EnemyState::Attack);
+                    self.attack_timer = self.attack_timer.max(ATTACK_COOLDOWN / 2.0);
+                } else if self.unseen_time > LOSE_SIGHT_TIME {
+                    self.give_up();
+                }
+            },
+            EnemyState::Attack => {
+                if !sees_player || distance >= ATTACK_RANGE {
+                    self.set_state(EnemyState::Chase);
+                }
+            },
+        }
+
+        // Behaviour for the current state
+        let mut damage = 0;
+        match self.state {
+            EnemyState::Idle => {
+                let home = self.waypoints[0];
+                self.walk_towards(home, PATROL_SPEED, delta_time, map);
+            },
+            EnemyState::Patrol => {
+                let target = self.waypoints[self.waypoint];
+                if self.walk_towards(target, PATROL_SPEED, delta_time, map) {
+                    self.waypoint = (self.waypoint + 1) % self.waypoints.len();
+                }
+            },
+            EnemyState::Chase => {
+                let target = self.last_seen;
+                self.walk_towards(target, CHASE_SPEED, delta_time, map);
+            },
+            EnemyState::Attack => {
+                if can_hurt && self.attack_timer <= 0.0 {
+                    damage += ATTACK_DAMAGE;
+                    self.attack_timer = ATTACK_COOLDOWN;
+                }
+            },
+        }
+
+        if can_hurt && distance < CONTACT_RANGE && self.contact_timer <= 0.0 {
+            damage += CONTACT_DAMAGE;
+            self.contact_timer = CONTACT_COOLDOWN;
+        }
+
+        self.sprite.x = self.x;
+        self.sprite.y = self.y;
+        self.sprite.update(delta_time);
+        damage
+    }
+
+    fn set_state(&mut self, state: EnemyState) {
+        self.state = state;
+        self.repath_timer = 0.0;
+        self.sprite.frames = match state {
+            EnemyState::Idle => vec![SPRITE_GUARD_WALK[0]],
+            EnemyState::Attack => vec![SPRITE_GUARD_ATTACK],
+            EnemyState::Patrol | EnemyState::Chase => SPRITE_GUARD_WALK.to_vec(),
+        };
+        self.sprite.frame_duration = if state == EnemyState::Chase { 0.2 } else { 0.35 };
+    }
+
+    // Lost the player, go back to guarding or patrolling
+    fn give_up(&mut self) {
+        let state = if self.waypoints.len() > 1 { EnemyState::Patrol } else { EnemyState::Idle };
+        self.set_state(state);
+        self.path.clear();
+    }
+
+    // Follows a grid path towards the target, returns true once standing on it
+    fn walk_towards(&mut self, target: (f64, f64), speed: f64, delta_time: f64, map: &Map) -> bool {
+        let goal = (target.0 as usize, target.1 as usize);
+        if self.repath_timer <= 0.0 || goal != self.path_goal {
+            let start = (self.x as usize, self.y as usize);
+            self.path = find_path(map, start, goal, &PathOptions::walking(Connectivity::Eight)).map_or_else(Vec::new, |path| path.cells);
+            self.path.reverse();
+            self.path_goal = goal;
+            self.repath_timer = REPATH_INTERVAL;
+        }
+
+        // Cell centres along the way, then the exact target inside the last cell
+        let (next_x, next_y) = match self.path.last() {
+            Some(&(cell_x, cell_y)) if self.path.len() > 1 => (cell_x as f64 + 0.5, cell_y as f64 + 0.5),
+            _ => target,
+        };
+
+        let dx = next_x - self.x;
+        let dy = next_y - self.y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        let step = speed * delta_time;
+        if distance <= step {
+            self.move_to(next_x, next_y, map);
+            if self.path.len() > 1 {
+                self.path.pop();
+                return false;
+            }
+            return true;
+        }
+        self.move_to(self.x + dx / distance * step, self.y + dy / distance * step, map);
+        false
+    }
+
+    // Same circle collision as the player, so enemies slide along walls and get pushed out of
+    // a door that closed on them
+    fn move_to(&mut self, new_x: f64, new_y: f64, map: &Map) {
+        let (x, y, _) = move_circle(map, self.x, self.y, ENEMY_RADIUS, new_x - self.x, new_y - self.y);
+        self.x = x;
+        self.y = y;
+    }
+}
+
+// Names used by level files
+pub fn enemy_by_name(name: &str, x: f64, y: f64, waypoints: Vec<(f64, f64)>) -> Option<Enemy> {
+    match name {
+        "guard" => Some(Enemy::new(x, y, waypoints)),
+        _ => None,
+    }
+}
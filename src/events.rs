@@ -0,0 +1,45 @@
+// Things that happened during a gameplay tick. Gameplay only pushes them onto the queue; at the
+// end of the tick Game::dispatch_events hands each one to the audio, effects, UI and level stats,
+// in the order they happened. A new reaction to an event goes into one of the handle_event
+// methods instead of the gameplay code.
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameEvent {
+    PlayerDamaged { kind: DamageKind, health_lost: i32, armor_lost: i32 },
+    PlayerDied,
+    LevelCompleted,
+    DoorOpened { x: usize, y: usize },
+    DoorLocked(KeyColor), // The player tried a door without its key
+    ItemPicked(ItemKind),
+    FlashlightToggled { on: bool },
+    BatteryFlat, // The flashlight could not turn on or just ran out
+    Footstep,
+}
+
+#[derive(Default)]
+pub struct EventQueue {
+    pending: Vec<GameEvent>,
+    pub last_tick: Vec<GameEvent>, // Everything dispatched at the end of the last tick
+}
+
+impl EventQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: GameEvent) {
+        self.pending.push(event);
+    }
+
+    // Takes the events pushed so far, they stay readable in last_tick until the next dispatch
+    pub fn take(&mut self) -> Vec<GameEvent> {
+        self.last_tick = std::mem::take(&mut self.pending);
+        self.last_tick.clone()
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+        self.last_tick.clear();
+    }
+}
@@ -1,171 +1,387 @@
 use crate::*;
-use minifb::{Key, Window};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::time::Instant;
 
+// How far in front of the player the use key reaches, in cells
+const USE_REACH: f64 = 1.5;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum GameState {
     Splash,
     LevelSelect,
     Playing,
+    Paused,
     Success,
     GameOver,
+    Controls,
+    Options,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PauseItem {
+    Resume,
+    Restart,
+    Options,
+    QuitToMenu,
+}
+
+pub const PAUSE_ITEMS: [PauseItem; 4] = [PauseItem::Resume, PauseItem::Restart, PauseItem::Options, PauseItem::QuitToMenu];
+
+impl PauseItem {
+    pub fn label(self) -> &'static str {
+        match self {
+            PauseItem::Resume => "Resume",
+            PauseItem::Restart => "Restart level",
+            PauseItem::Options => "Options",
+            PauseItem::QuitToMenu => "Quit to menu",
+        }
+    }
 }
 
 pub struct Game {
     pub state: GameState,
     pub player: Player,
     pub map: Map,
+    pub levels: LevelCatalog,
+    pub endless: Option<EndlessRun>, // Set while playing generated levels
+    pub sprites: Vec<Sprite>,
+    pub items: Vec<Item>,
+    pub enemies: Vec<Enemy>,
     pub raycaster: RayCaster,
     pub audio: AudioSystem,
     pub ui: UI,
     pub effects: Effects,
+    pub events: EventQueue, // Emitted by gameplay, dispatched at the end of each tick
     pub buffer: Vec<u32>,
+    pub view_buffer: Vec<u32>, // 3D view below full resolution, before it is stretched into buffer
+    pub show_hud: bool, // Minimap and HUD on top of the 3D view
+    pub bindings: KeyBindings,
+    pub controls_menu: ControlsMenu,
+    pub settings: Settings,
+    pub options_menu: OptionsMenu,
+    pub options_return: GameState, // Screen the options menu goes back to
+    pub pause_selected: usize, // Row in PAUSE_ITEMS
+    pub quit_requested: bool,
+    pub persist: bool, // Write changes to the player's files; off for headless renders and replays
+    pub seed: u64,    // Seed of rng, so a run can be played back
+    pub rng: StdRng,  // All randomness that affects the game state comes from here
+    pub previous_view: (f64, f64, f64), // Player x, y and angle before the last tick
+    pub interpolation: f64, // How far render is between previous_view and the player, 0.0 - 1.0
+    pub progress: SaveData,
+    pub level_time: f64,   // Seconds spent in the current level
+    pub damage_taken: i32, // Health lost in the current level
+    pub result: LevelResult, // How the last finished level went
+    pub leaderboard: Leaderboard,
+    pub name_entry: Option<NameEntry>, // Set while typing a name for a new high score
     pub current_level: usize,
+    pub difficulty: Difficulty, // Of the level being played
     pub player_health: i32,
+    pub max_health: i32,
+    pub armor: i32,
+    pub invulnerable_time: f64, // Seconds left before other sources than the last hit can hurt again
+    pub invulnerable_source: Option<DamageKind>, // What dealt the last hit
+    pub battery: f64, // Flashlight charge, 0 - MAX_BATTERY
     pub splash_timer: f64,
     pub level_complete: bool,
-    pub mouse_sensitivity: f64,
-    pub last_mouse_x: f64,
-    pub last_keys: std::collections::HashSet<Key>,
-    pub wall_damage_cooldown: f64,
-    pub hazard_damage_cooldown: f64,
+    pub wall_damage: DamageTimer,
+    pub hazard_damage: DamageTimer,
 }
 
 impl Game {
     pub fn new() -> Self {
-        let map = Map::new(0);
+        Self::with_audio(AudioSystem::new())
+    }
+
+    // Headless runs pass a silent audio system so no sound device is opened
+    pub fn with_audio(audio: AudioSystem) -> Self {
+        let mut game = Self::with_defaults(audio);
+        game.bindings = KeyBindings::load();
+        game.settings = Settings::load();
+        game.progress = SaveData::load();
+        game.leaderboard = Leaderboard::load();
+        game.persist = true;
+        game.apply_settings();
+        game
+    }
+
+    // Default controls, settings, progress and scores without touching the player's files, for
+    // headless renders and replays. Only the levels are read from disk.
+    pub fn with_defaults(audio: AudioSystem) -> Self {
+        let levels = LevelCatalog::load();
+        let map = Map::new(1, 1);
+        let sprites = Vec::new();
+        let items = Vec::new();
+        let enemies = Vec::new();
         let player = Player::new(1.5, 1.5, 0.0);
         let raycaster = RayCaster::new();
-        let audio = AudioSystem::new();
         let ui = UI::new();
         let effects = Effects::new();
         
-        Self {
+        let mut game = Self {
             state: GameState::Splash,
             player,
             map,
+            levels,
+            endless: None,
+            sprites,
+            items,
+            enemies,
             raycaster,
             audio,
             ui,
             effects,
+            events: EventQueue::new(),
             buffer: vec![0; WIDTH * HEIGHT],
+            view_buffer: Vec::new(),
+            show_hud: true,
+            bindings: KeyBindings::defaults(),
+            controls_menu: ControlsMenu::new(),
+            settings: Settings::defaults(),
+            options_menu: OptionsMenu::new(),
+            options_return: GameState::LevelSelect,
+            pause_selected: 0,
+            quit_requested: false,
+            persist: false,
+            seed: 0,
+            rng: StdRng::seed_from_u64(0),
+            previous_view: (1.5, 1.5, 0.0),
+            interpolation: 1.0,
+            progress: SaveData::new(),
+            level_time: 0.0,
+            damage_taken: 0,
+            result: LevelResult::default(),
+            leaderboard: Leaderboard::new(),
+            name_entry: None,
             current_level: 0,
+            difficulty: Difficulty::default(),
             player_health: 100,
+            max_health: 100,
+            armor: 0,
+            invulnerable_time: 0.0,
+            invulnerable_source: None,
+            battery: MAX_BATTERY,
             splash_timer: 0.0,
             level_complete: false,
-            mouse_sensitivity: 0.002,
-            last_mouse_x: WIDTH as f64 / 2.0,
-            last_keys: std::collections::HashSet::new(),
-            wall_damage_cooldown: 0.0,
-            hazard_damage_cooldown: 0.0,
-        }
+            wall_damage: DamageTimer::default(),
+            hazard_damage: DamageTimer::default(),
+        };
+        game.apply_settings();
+        game.reseed(rand::random());
+        game
     }
 
-    pub fn update(&mut self, delta_time: f64, window: &Window, mouse_pos: (f32, f32)) {
-        // Helper function to check if key was just pressed
-        let key_just_pressed = |key: Key, last_keys: &std::collections::HashSet<Key>| {
-            window.is_key_down(key) && !last_keys.contains(&key)
-        };
-        
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    // Pushes the settings into the systems that use them. The window side (fullscreen,
+    // resolution scale, mouse) is read by the main loop every frame.
+    pub fn apply_settings(&mut self) {
+        self.raycaster.fov = self.settings.fov.to_radians();
+        self.effects.fog_enabled = self.settings.fog;
+        self.effects.damage_flash_enabled = self.settings.damage_flash;
+        self.audio.set_music_volume(self.settings.music_volume as f32);
+        self.audio.set_sfx_volume(self.settings.sfx_volume as f32);
+    }
+
+    // Advances the game by one simulation tick, always TICK seconds in the window
+    pub fn update(&mut self, delta_time: f64, input: &InputState) {
+        self.previous_view = (self.player.x, self.player.y, self.player.angle);
+
+        // Switching to another window pauses the level so the mouse is released
+        if input.focus_lost {
+            self.pause();
+        }
+
+        // Escape leaves the game from the title and result screens. A level in progress pauses
+        // instead and the menus use it to go back.
+        let quits = matches!(self.state, GameState::Splash | GameState::LevelSelect | GameState::Success | GameState::GameOver)
+            && self.name_entry.is_none();
+        if input.is_pressed(Action::Back) && quits {
+            self.quit_requested = true;
+        }
+
         match self.state {
             GameState::Splash => {
                 self.splash_timer += delta_time;
-                if self.splash_timer > 3.0 || key_just_pressed(Key::Space, &self.last_keys) {
+                if self.splash_timer > 3.0 || input.is_pressed(Action::Confirm) {
                     self.state = GameState::LevelSelect;
                     self.audio.play_menu_music();
                 }
             },
             GameState::LevelSelect => {
-                if key_just_pressed(Key::Key1, &self.last_keys) {
-                    self.start_level(0);
-                } else if key_just_pressed(Key::Key2, &self.last_keys) {
-                    self.start_level(1);
-                } else if key_just_pressed(Key::Key3, &self.last_keys) {
-                    self.start_level(2);
+                for level in 0..self.levels.len().min(LEVEL_SLOTS) {
+                    if input.is_pressed(Action::SelectLevel(level)) {
+                        if self.progress.is_unlocked(&self.levels, level) {
+                            self.difficulty = self.progress.difficulty;
+                            self.start_level(level);
+                        } else {
+                            self.ui.level_error = Some(format!("Finish {} to unlock {}", self.levels.levels[level - 1].name, self.levels.levels[level].name));
+                        }
+                        break;
+                    }
+                }
+                if input.is_pressed(Action::Confirm) && self.progress.snapshot.is_some() {
+                    self.continue_level();
+                } else if input.is_pressed(Action::Endless) {
+                    let seed = self.rng.gen::<u32>() as u64;
+                    self.difficulty = self.progress.difficulty;
+                    self.endless = Some(EndlessRun::new(seed));
+                    self.start_endless_level();
+                    self.ui.show_message(&format!("Endless run seed {}", seed));
+                } else if input.is_pressed(Action::MenuLeft) || input.is_pressed(Action::MenuRight) {
+                    let steps = if input.is_pressed(Action::MenuLeft) { -1 } else { 1 };
+                    self.progress.difficulty = self.progress.difficulty.step(steps);
+                    self.save_progress();
+                } else if input.is_pressed(Action::Controls) {
+                    self.controls_menu = ControlsMenu::new();
+                    self.state = GameState::Controls;
+                } else if input.is_pressed(Action::Options) {
+                    self.open_options(GameState::LevelSelect);
+                }
+            },
+            GameState::Controls => {
+                if !self.controls_menu.update(input, &mut self.bindings, self.persist) {
+                    self.state = GameState::LevelSelect;
+                }
+            },
+            GameState::Options => {
+                let open = self.options_menu.update(input, &mut self.settings, self.persist);
+                self.apply_settings();
+                if !open {
+                    self.state = self.options_return;
                 }
             },
             GameState::Playing => {
-                self.update_gameplay(delta_time, window, mouse_pos);
+                if input.is_pressed(Action::Back) {
+                    self.pause();
+                } else {
+                    self.update_gameplay(delta_time, input);
+                }
+            },
+            GameState::Paused => {
+                if input.is_pressed(Action::Back) {
+                    self.resume();
+                } else if input.is_pressed(Action::MenuUp) {
+                    self.pause_selected = (self.pause_selected + PAUSE_ITEMS.len() - 1) % PAUSE_ITEMS.len();
+                } else if input.is_pressed(Action::MenuDown) {
+                    self.pause_selected = (self.pause_selected + 1) % PAUSE_ITEMS.len();
+                } else if input.is_pressed(Action::MenuSelect) || input.is_pressed(Action::Confirm) {
+                    match PAUSE_ITEMS[self.pause_selected] {
+                        PauseItem::Resume => self.resume(),
+                        PauseItem::Restart => self.restart_level(),
+                        PauseItem::Options => self.open_options(GameState::Paused),
+                        PauseItem::QuitToMenu => {
+                            self.suspend_level();
+                            self.return_to_menu();
+                        },
+                    }
+                }
             },
             GameState::Success => {
-                if key_just_pressed(Key::Space, &self.last_keys) {
-                    self.state = GameState::LevelSelect;
-                    self.audio.play_menu_music();
+                if let Some(entry) = &mut self.name_entry {
+                    match entry.update(input) {
+                        EntryStatus::Editing => {},
+                        EntryStatus::Submitted => self.submit_score(),
+                        EntryStatus::Cancelled => self.name_entry = None,
+                    }
+                } else if input.is_pressed(Action::Confirm) {
+                    // Endless runs carry straight on to the next, larger level
+                    if let Some(run) = &mut self.endless {
+                        run.depth += 1;
+                        self.start_endless_level();
+                    } else {
+                        self.state = GameState::LevelSelect;
+                        self.audio.play_menu_music();
+                    }
                 }
             },
             GameState::GameOver => {
-                if key_just_pressed(Key::R, &self.last_keys) {
+                if input.is_pressed(Action::Restart) {
                     self.restart_level();
-                } else if key_just_pressed(Key::M, &self.last_keys) {
-                    self.state = GameState::LevelSelect;
-                    self.audio.play_menu_music();
+                } else if input.is_pressed(Action::Menu) {
+                    self.return_to_menu();
                 }
             },
         }
         
-        // Update key tracking
-        self.last_keys.clear();
-        for key in [Key::Space, Key::Key1, Key::Key2, Key::Key3, Key::R, Key::M, Key::F].iter() {
-            if window.is_key_down(*key) {
-                self.last_keys.insert(*key);
-            }
-        }
-        
         self.effects.update(delta_time);
     }
 
-    fn update_gameplay(&mut self, delta_time: f64, window: &Window, mouse_pos: (f32, f32)) {
-        // Mouse rotation (horizontal only) - mouse_pos.0 is already the delta
-        let mouse_delta_x = mouse_pos.0 as f64;
-        self.player.angle += mouse_delta_x;
+    // Freezes a level in progress; does nothing on other screens
+    fn pause(&mut self) {
+        if self.state == GameState::Playing {
+            self.state = GameState::Paused;
+            self.pause_selected = 0;
+            self.audio.pause_music();
+        }
+    }
+
+    fn resume(&mut self) {
+        self.state = GameState::Playing;
+        self.audio.resume_music();
+    }
+
+    fn open_options(&mut self, return_to: GameState) {
+        self.options_menu = OptionsMenu::new();
+        self.options_return = return_to;
+        self.state = GameState::Options;
+    }
+
+    fn return_to_menu(&mut self) {
+        self.endless = None;
+        self.state = GameState::LevelSelect;
+        self.audio.play_menu_music();
+    }
+
+    fn update_gameplay(&mut self, delta_time: f64, input: &InputState) {
+        self.level_time += delta_time;
+
+        // Mouse rotation (horizontal only)
+        self.player.angle += input.mouse_delta.0;
 
         // Player movement
         let mut move_x = 0.0;
         let mut move_y = 0.0;
         
-        if window.is_key_down(Key::W) || window.is_key_down(Key::Up) {
+        if input.is_held(Action::MoveForward) {
             move_x += self.player.angle.cos();
             move_y += self.player.angle.sin();
         }
-        if window.is_key_down(Key::S) || window.is_key_down(Key::Down) {
+        if input.is_held(Action::MoveBackward) {
             move_x -= self.player.angle.cos();
             move_y -= self.player.angle.sin();
         }
-        if window.is_key_down(Key::A) || window.is_key_down(Key::Left) {
+        if input.is_held(Action::StrafeLeft) {
             move_x += (self.player.angle - PI/2.0).cos();
             move_y += (self.player.angle - PI/2.0).sin();
         }
-        if window.is_key_down(Key::D) || window.is_key_down(Key::Right) {
+        if input.is_held(Action::StrafeRight) {
             move_x += (self.player.angle + PI/2.0).cos();
             move_y += (self.player.angle + PI/2.0).sin();
         }
 
         // Normalize movement vector
         let move_length = (move_x * move_x + move_y * move_y).sqrt();
+        let mut hit_wall = false;
         if move_length > 0.0 {
             move_x /= move_length;
             move_y /= move_length;
             
-            let hit_wall = self.player.update(delta_time, move_x, move_y, &self.map);
-            
-            // Wall collision damage with cooldown
-            if hit_wall && self.wall_damage_cooldown <= 0.0 {
-                let damage = 5; // Fixed damage per collision
-                self.player_health -= damage;
-                self.effects.trigger_damage_effect();
-                self.wall_damage_cooldown = 0.5; // 0.5 second cooldown
-                
-                if self.player_health <= 0 {
-                    self.state = GameState::GameOver;
-                    self.audio.play_death_sound();
-                }
-            }
+            hit_wall = self.player.update(delta_time, move_x, move_y, &self.map);
             
             if move_length > 0.1 {
-                self.audio.play_footstep();
+                self.events.push(GameEvent::Footstep);
             }
         }
 
+        // Wall collision damage, by the level's rules
+        let vulnerable = self.vulnerable_to(DamageKind::Impact);
+        let damage = self.wall_damage.tick(self.map.damage_rules.wall, hit_wall, vulnerable, delta_time);
+        self.apply_damage(DamageKind::Impact, damage);
+
         // Optimized level completion check
         let player_grid_x = self.player.x as usize;
         let player_grid_y = self.player.y as usize;
@@ -179,55 +395,353 @@ impl Game {
         if found_exit {
             self.level_complete = true;
             self.state = GameState::Success;
-            self.audio.play_success_sound();
+            self.events.push(GameEvent::LevelCompleted);
         }
 
-        // Damage from hazards (orange zones) with cooldown
-        if self.map.is_hazard(self.player.x as usize, self.player.y as usize) && self.hazard_damage_cooldown <= 0.0 {
-            let damage = 10; // Fixed damage per hazard tick
-            self.player_health -= damage;
-            self.effects.trigger_damage_effect();
-            self.hazard_damage_cooldown = 0.3; // 0.3 second cooldown (faster than wall damage)
-            
-            if self.player_health <= 0 {
-                self.state = GameState::GameOver;
-                self.audio.play_death_sound();
+        // Damage from hazards (orange zones), by the level's rules
+        let on_hazard = self.map.is_hazard(self.player.x as usize, self.player.y as usize);
+        let vulnerable = self.vulnerable_to(DamageKind::Fire);
+        let damage = self.hazard_damage.tick(self.map.damage_rules.hazard, on_hazard, vulnerable, delta_time);
+        self.apply_damage(DamageKind::Fire, damage);
+
+        // Toggle flashlight, it only lights while the battery lasts
+        if input.is_pressed(Action::Flashlight) {
+            if self.battery > 0.0 || self.effects.flashlight_enabled {
+                self.effects.toggle_flashlight();
+                self.events.push(GameEvent::FlashlightToggled { on: self.effects.flashlight_enabled });
+            } else {
+                self.events.push(GameEvent::BatteryFlat);
             }
         }
+        if self.effects.flashlight_enabled {
+            self.battery = (self.battery - FLASHLIGHT_DRAIN * delta_time).max(0.0);
+            if self.battery == 0.0 {
+                self.effects.toggle_flashlight();
+                self.events.push(GameEvent::FlashlightToggled { on: false });
+                self.events.push(GameEvent::BatteryFlat);
+            }
+        }
+        
+        // Enemies hunt the player and hit back on contact or attack, holding their blows while
+        // the player cannot be hurt
+        let vulnerable = self.vulnerable_to(DamageKind::Enemy);
+        let mut enemy_damage = 0;
+        for enemy in &mut self.enemies {
+            enemy_damage += enemy.update(delta_time, &self.player, &self.map, &self.raycaster, vulnerable);
+        }
+        self.apply_damage(DamageKind::Enemy, enemy_damage);
 
-        // Toggle flashlight
-        if window.is_key_down(Key::F) && !self.last_keys.contains(&Key::F) {
-            self.effects.toggle_flashlight();
-            println!("Flashlight toggled: {}", self.effects.flashlight_enabled);
+        // Pick up items the player walks over. Consumables stay on the floor while they would
+        // be wasted.
+        for item in &mut self.items {
+            if !item.is_touching(self.player.x, self.player.y) {
+                continue;
+            }
+            match item.kind {
+                ItemKind::Key(color) => self.player.inventory.add_key(color),
+                ItemKind::Medkit if self.player_health < self.max_health => {
+                    self.player_health = (self.player_health + MEDKIT_HEALTH).min(self.max_health);
+                },
+                ItemKind::Armor if self.armor < MAX_ARMOR => {
+                    self.armor = (self.armor + ARMOR_POINTS).min(MAX_ARMOR);
+                },
+                ItemKind::Battery if self.battery < MAX_BATTERY => {
+                    self.battery = (self.battery + BATTERY_CHARGE).min(MAX_BATTERY);
+                },
+                _ => continue,
+            }
+            item.collected = true;
+            self.events.push(GameEvent::ItemPicked(item.kind));
         }
         
+        // Open the door the player is facing, locked doors need their key
+        if input.is_pressed(Action::Use) {
+            if let Some((door_x, door_y)) = self.map.find_door_in_front(self.player.x, self.player.y, self.player.angle, USE_REACH) {
+                let lock = self.map.get_door(door_x, door_y).and_then(|door| door.lock);
+                match lock {
+                    Some(color) if !self.player.inventory.has_key(color) => {
+                        self.events.push(GameEvent::DoorLocked(color));
+                    },
+                    _ => {
+                        if let Some(door) = self.map.get_door_mut(door_x, door_y) {
+                            door.lock = None;
+                        }
+                        if self.map.open_door(door_x, door_y) {
+                            self.events.push(GameEvent::DoorOpened { x: door_x, y: door_y });
+                        }
+                    },
+                }
+            }
+        }
+        
+        // Animate doors, which close by themselves unless the player or an enemy stands in the doorway
+        let mut occupied = vec![(self.player.x as usize, self.player.y as usize)];
+        occupied.extend(self.enemies.iter().map(|enemy| (enemy.x as usize, enemy.y as usize)));
+        self.map.update_doors(delta_time, &occupied);
+        
+        // Advance sprite animations
+        for sprite in &mut self.sprites {
+            sprite.update(delta_time);
+        }
+        
+        if self.invulnerable_time > 0.0 {
+            self.invulnerable_time -= delta_time;
+        }
+        
+        self.dispatch_events();
+        
         // Update effects
         self.effects.update(delta_time);
-        
-        // Update wall damage cooldown
-        if self.wall_damage_cooldown > 0.0 {
-            self.wall_damage_cooldown -= delta_time;
+        self.ui.update(delta_time);
+        self.audio.update(delta_time);
+    }
+
+    // Right after a hit the other sources cannot hurt the player for a moment, so overlapping
+    // sources do not stack. The source that hit keeps its own pace, set by its cooldown.
+    fn vulnerable_to(&self, kind: DamageKind) -> bool {
+        self.invulnerable_time <= 0.0 || self.invulnerable_source == Some(kind)
+    }
+
+    // The one place the player gets hurt. Armor takes its share first.
+    fn apply_damage(&mut self, kind: DamageKind, amount: i32) {
+        if amount <= 0 || !self.vulnerable_to(kind) || self.state != GameState::Playing {
+            return;
         }
+        let (health_lost, armor_lost) = kind.absorb(amount, self.armor);
+        self.armor -= armor_lost;
+        self.player_health -= health_lost;
+        self.invulnerable_time = INVULNERABILITY_TIME;
+        self.invulnerable_source = Some(kind);
+        self.events.push(GameEvent::PlayerDamaged { kind, health_lost, armor_lost });
         
-        // Update hazard damage cooldown
-        if self.hazard_damage_cooldown > 0.0 {
-            self.hazard_damage_cooldown -= delta_time;
+        if self.player_health <= 0 {
+            self.state = GameState::GameOver;
+            self.events.push(GameEvent::PlayerDied);
+        }
+    }
+
+    // Hands this tick's events to everything that reacts to them, in the order they happened
+    fn dispatch_events(&mut self) {
+        for event in self.events.take() {
+            self.audio.handle_event(&event);
+            self.effects.handle_event(&event);
+            self.ui.handle_event(&event);
+            self.record_event(&event);
+        }
+    }
+
+    // Level stats: damage taken and, once the exit is reached, the result and best scores
+    fn record_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::PlayerDamaged { health_lost, .. } => self.damage_taken += health_lost,
+            GameEvent::LevelCompleted => self.record_completion(),
+            _ => {},
         }
     }
 
     fn start_level(&mut self, level: usize) {
+        let map = match self.levels.load_map(level) {
+            Ok(map) => map,
+            Err(e) => {
+                println!("Error: {}", e);
+                self.ui.level_error = Some(e.to_string());
+                return;
+            }
+        };
+        
+        self.endless = None;
         self.current_level = level;
-        self.map = Map::new(level);
-        self.player = Player::new(1.5, 1.5, 0.0);
-        self.player_health = 100;
+        self.begin_level(map);
+    }
+
+    fn start_endless_level(&mut self) {
+        if let Some(run) = self.endless {
+            self.begin_level(run.generate());
+        }
+    }
+
+    // Starts a level on the difficulty in self.difficulty
+    pub fn begin_level(&mut self, mut map: Map) {
+        // Refuse to start a level that would drop the player into a wall or hazard
+        if let Err(e) = map.validate_spawn() {
+            println!("Error: {}: {}", map.name, e);
+            self.ui.level_error = Some(format!("{}: {}", map.name, e));
+            return;
+        }
+        map.damage_rules = self.difficulty.damage_rules(map.damage_rules);
+        
+        self.map = map;
+        self.sprites = self.map.sprites.clone();
+        self.items = self.map.items.clone();
+        self.enemies = self.map.enemies.clone();
+        self.player = Player::new(self.map.spawn_x, self.map.spawn_y, self.map.spawn_angle);
+        self.player.radius = self.map.player_radius;
+        self.previous_view = (self.player.x, self.player.y, self.player.angle); // No blending from the last level
+        self.max_health = self.difficulty.starting_health();
+        self.player_health = self.max_health;
+        self.armor = 0;
+        self.invulnerable_time = 0.0;
+        self.invulnerable_source = None;
+        self.battery = MAX_BATTERY;
+        self.raycaster.max_depth = self.difficulty.view_distance();
+        self.level_time = 0.0;
+        self.damage_taken = 0;
+        self.wall_damage = DamageTimer::default();
+        self.hazard_damage = DamageTimer::default();
+        self.name_entry = None;
         self.level_complete = false;
+        self.ui.level_error = None;
+        self.ui.message = None;
         self.state = GameState::Playing;
-        self.audio.play_game_music();
+        self.audio.play_game_music(&self.map.music);
         self.effects.reset();
+        self.events.clear();
+        self.effects.flashlight_strength = self.difficulty.flashlight_strength();
+    }
+
+    // Picks the level saved by suspend_level back up where it was left. The saved run is only
+    // used up once its level has started, so a missing or broken level file does not lose it.
+    fn continue_level(&mut self) {
+        let snapshot = match self.progress.snapshot.clone() {
+            Some(snapshot) => snapshot,
+            None => return,
+        };
+
+        let index = match self.levels.levels.iter().position(|level| level.name == snapshot.level) {
+            Some(index) => index,
+            None => {
+                self.ui.level_error = Some(format!("The saved level {} no longer exists", snapshot.level));
+                return;
+            }
+        };
+        self.difficulty = snapshot.difficulty;
+        self.start_level(index);
+        if self.state != GameState::Playing {
+            return;
+        }
+        self.progress.snapshot = None;
+        self.save_progress();
+
+        // The level file may have changed since, so a position inside a wall falls back to the spawn
+        if !circle_blocked(&self.map, snapshot.x, snapshot.y, self.player.radius) {
+            self.player.x = snapshot.x;
+            self.player.y = snapshot.y;
+            self.player.angle = snapshot.angle;
+            self.previous_view = (snapshot.x, snapshot.y, snapshot.angle);
+        }
+        self.player_health = snapshot.health;
+        self.armor = snapshot.armor;
+        self.battery = snapshot.battery;
+        self.level_time = snapshot.time;
+        self.damage_taken = snapshot.damage_taken;
+        self.player.inventory.keys = snapshot.keys;
+        for &index in &snapshot.collected {
+            if let Some(item) = self.items.get_mut(index) {
+                item.collected = true;
+            }
+        }
+        for &(x, y) in &snapshot.unlocked_doors {
+            if let Some(door) = self.map.get_door_mut(x, y) {
+                door.lock = None;
+            }
+        }
+    }
+
+    // Keeps a catalog level in progress in the save file so it can be continued next time
+    pub fn suspend_level(&mut self) {
+        let in_level = match self.state {
+            GameState::Playing | GameState::Paused => true,
+            GameState::Options => self.options_return == GameState::Paused,
+            _ => false,
+        };
+        if !in_level || self.endless.is_some() {
+            return;
+        }
+
+        let mut unlocked_doors: Vec<(usize, usize)> = self.map.doors.iter()
+            .filter(|(_, door)| door.lock.is_none())
+            .map(|(&cell, _)| cell)
+            .collect();
+        unlocked_doors.sort();
+        self.progress.snapshot = Some(Snapshot {
+            level: self.map.name.clone(),
+            x: self.player.x,
+            y: self.player.y,
+            angle: self.player.angle,
+            health: self.player_health,
+            armor: self.armor,
+            battery: self.battery,
+            time: self.level_time,
+            damage_taken: self.damage_taken,
+            difficulty: self.difficulty,
+            keys: self.player.inventory.keys.clone(),
+            collected: (0..self.items.len()).filter(|&index| self.items[index].collected).collect(),
+            unlocked_doors,
+        });
+        self.save_progress();
+    }
+
+    fn record_completion(&mut self) {
+        self.result = LevelResult {
+            time: self.level_time,
+            health: self.player_health,
+            damage_taken: self.damage_taken,
+            score: level_score(self.level_time, self.player_health),
+            difficulty: self.difficulty,
+            ..LevelResult::default()
+        };
+        if let Some(run) = &self.endless {
+            self.progress.endless_best_depth = self.progress.endless_best_depth.max(run.depth + 1);
+        } else {
+            // Generated levels have no best time or score table
+            self.result.best_time = self.progress.record(&self.map.name).best_time;
+            self.result.new_best = self.progress.complete_level(&self.levels, self.current_level, self.level_time);
+            if self.leaderboard.qualifies(&self.map.name, self.difficulty, self.result.score) {
+                self.name_entry = Some(NameEntry::new(&self.leaderboard.last_name));
+            }
+        }
+        // A level left earlier and now finished from the start has nothing left to continue
+        if self.progress.snapshot.as_ref().is_some_and(|snapshot| snapshot.level == self.map.name) {
+            self.progress.snapshot = None;
+        }
+        self.save_progress();
+    }
+
+    fn submit_score(&mut self) {
+        let name = match self.name_entry.take() {
+            Some(entry) => entry.name,
+            None => return,
+        };
+        let entry = ScoreEntry {
+            name,
+            score: self.result.score,
+            time: self.result.time,
+            damage_taken: self.result.damage_taken,
+            difficulty: self.result.difficulty,
+        };
+        self.result.rank = self.leaderboard.insert(&self.map.name, entry);
+        if self.persist {
+            if let Err(e) = self.leaderboard.save() {
+                eprintln!("Warning: could not save {}: {}", SCORES_FILE, e);
+            }
+        }
+    }
+
+    fn save_progress(&self) {
+        if !self.persist {
+            return;
+        }
+        if let Err(e) = self.progress.save() {
+            eprintln!("Warning: could not save {}: {}", SAVE_FILE, e);
+        }
     }
 
     fn restart_level(&mut self) {
-        self.start_level(self.current_level);
+        if self.endless.is_some() {
+            self.start_endless_level();
+        } else {
+            self.start_level(self.current_level);
+        }
     }
 
     pub fn render(&mut self, window_width: usize, window_height: usize) -> &Vec<u32> {
@@ -244,35 +758,137 @@ impl Game {
 
         match self.state {
             GameState::Splash => {
-                self.ui.render_splash_screen(&mut self.buffer, window_width, window_height);
+                self.ui.render_splash_screen(&mut self.buffer, &self.bindings, window_width, window_height);
             },
             GameState::LevelSelect => {
-                self.ui.render_level_select(&mut self.buffer, window_width, window_height);
+                self.ui.render_level_select(&mut self.buffer, &self.levels, &self.progress, &self.bindings, window_width, window_height);
             },
             GameState::Playing => {
-                // Render 3D view
-                self.raycaster.render(&mut self.buffer, &self.player, &self.map, &self.effects, window_width, window_height);
-                
-                // Render optimized minimap
-                self.ui.render_minimap(&mut self.buffer, &self.player, &self.map, window_width, window_height);
-                
-                // Render HUD
-                self.ui.render_hud(&mut self.buffer, self.player_health, window_width, window_height);
-                
-                // Apply visual effects for damage feedback
-                self.effects.apply_effects(&mut self.buffer);
-                
-                // Apply flashlight overlay effect
-                self.effects.apply_flashlight_overlay(&mut self.buffer, &self.player, window_width, window_height);
+                self.render_world(window_width, window_height);
+            },
+            GameState::Paused => {
+                self.render_world(window_width, window_height);
+                self.ui.render_pause_menu(&mut self.buffer, self.pause_selected, window_width, window_height);
             },
             GameState::Success => {
-                self.ui.render_success_screen(&mut self.buffer, window_width, window_height);
+                // Generated levels have no score table
+                let table = match self.endless {
+                    Some(_) => &[],
+                    None => self.leaderboard.table(&self.map.name, self.difficulty),
+                };
+                let view = SuccessView { result: &self.result, table, name_entry: self.name_entry.as_ref() };
+                self.ui.render_success_screen(&mut self.buffer, &self.bindings, &view, window_width, window_height);
             },
             GameState::GameOver => {
-                self.ui.render_game_over_screen(&mut self.buffer, window_width, window_height);
+                self.ui.render_game_over_screen(&mut self.buffer, &self.bindings, window_width, window_height);
+            },
+            GameState::Controls => {
+                self.ui.render_controls_menu(&mut self.buffer, &self.bindings, &self.controls_menu, window_width, window_height);
+            },
+            GameState::Options => {
+                self.ui.render_options_menu(&mut self.buffer, &self.settings, &self.options_menu, window_width, window_height);
             },
         }
 
         &self.buffer
     }
-}
\ No newline at end of file
+
+    // The level as the player sees it, with HUD and screen effects
+    fn render_world(&mut self, window_width: usize, window_height: usize) {
+        // Render 3D view, possibly at a lower resolution. The HUD stays sharp on top.
+        let view_width = ((window_width as f64 * self.settings.resolution_scale) as usize).max(1);
+        let view_height = ((window_height as f64 * self.settings.resolution_scale) as usize).max(1);
+        let camera = self.camera();
+        let scaled = (view_width, view_height) != (window_width, window_height);
+        let view = if scaled {
+            self.view_buffer.resize(view_width * view_height, 0);
+            &mut self.view_buffer
+        } else {
+            &mut self.buffer
+        };
+        self.raycaster.render(view, &camera, &self.map, &self.effects, view_width, view_height);
+        let uncollected = self.items.iter().filter(|item| !item.collected).map(|item| &item.sprite);
+        let enemies = self.enemies.iter().map(|enemy| &enemy.sprite);
+        let sprites: Vec<&Sprite> = self.sprites.iter().chain(uncollected).chain(enemies).collect();
+        self.raycaster.render_sprites(view, &camera, &sprites, &self.effects, view_width, view_height);
+        if scaled {
+            stretch(&self.view_buffer, view_width, view_height, &mut self.buffer, window_width, window_height);
+        }
+        
+        if self.show_hud {
+            // Render optimized minimap
+            let view = MinimapView { player: &self.player, map: &self.map, items: &self.items, enemies: &self.enemies };
+            self.ui.render_minimap(&mut self.buffer, &view, window_width, window_height);
+            
+            // Render HUD
+            let stats = HudStats {
+                health: self.player_health,
+                max_health: self.max_health,
+                armor: self.armor,
+                battery: self.battery,
+                inventory: &self.player.inventory,
+            };
+            self.ui.render_hud(&mut self.buffer, &stats, &self.bindings, window_width, window_height);
+        }
+        
+        // Apply visual effects for damage feedback
+        self.effects.apply_effects(&mut self.buffer);
+        
+        // Apply flashlight overlay effect
+        self.effects.apply_flashlight_overlay(&mut self.buffer, &camera, window_width, window_height);
+    }
+
+    // The player's view blended between the last two ticks, so movement looks smooth at any
+    // frame rate
+    fn camera(&self) -> Player {
+        let (x, y, angle) = self.previous_view;
+        let t = self.interpolation;
+        // Turn the short way round when the angle wraps
+        let turn = (self.player.angle - angle + PI).rem_euclid(2.0 * PI) - PI;
+        Player::new(x + (self.player.x - x) * t, y + (self.player.y - y) * t, angle + turn * t)
+    }
+}
+
+// Nearest-neighbour resize
+fn stretch(source: &[u32], source_width: usize, source_height: usize, target: &mut [u32], target_width: usize, target_height: usize) {
+    for y in 0..target_height {
+        let source_row = y * source_height / target_height * source_width;
+        for x in 0..target_width {
+            target[y * target_width + x] = source[source_row + x * source_width / target_width];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A corridor from the spawn over a hazard to the exit, with a red key on the hazard
+    const LEVEL: &str = "name: Events\n[map]\n11111\n1P651\n11111\n[items]\nkey red 2.5 1.5\n";
+
+    #[test]
+    fn a_tick_queues_its_events_in_order_and_dispatches_them() {
+        let mut game = Game::with_defaults(AudioSystem::silent());
+        game.begin_level(parse_level(LEVEL).unwrap());
+        assert!(game.state == GameState::Playing);
+        game.player.x = 2.5;
+
+        let mut input = InputState::new();
+        input.update([Action::Flashlight], Vec::new(), (0.0, 0.0));
+        game.update(TICK, &input);
+
+        let health_lost = game.max_health - game.player_health;
+        assert!(health_lost > 0);
+        assert_eq!(game.events.last_tick, vec![
+            GameEvent::PlayerDamaged { kind: DamageKind::Fire, health_lost, armor_lost: 0 },
+            GameEvent::FlashlightToggled { on: true },
+            GameEvent::ItemPicked(ItemKind::Key(KeyColor::Red)),
+        ]);
+
+        // Every handler saw them: stats, effects and the HUD message of the last one
+        assert_eq!(game.damage_taken, health_lost);
+        assert!(game.effects.flashlight_enabled);
+        assert_eq!(game.ui.message.as_ref().map(|(text, _)| text.as_str()), Some("Picked up the red key"));
+        assert!(game.events.take().is_empty());
+    }
+}
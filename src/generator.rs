@@ -0,0 +1,391 @@
+// Seeded level generation for endless mode. The same kind, size and seed always give the same map.
+use crate::*;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+
+const MIN_SIZE: usize = 7;
+const MAX_SIZE: usize = 63; // Keeps at least two minimap pixels per cell
+const REGION_SIZE: usize = 6; // Walls in the same block of cells share a wall type
+const WALL_TYPES: [CellType; 4] = [CellType::Wall1, CellType::Wall2, CellType::Wall3, CellType::Wall4];
+const FLOORS: [usize; 3] = [FLOOR_TILES, FLOOR_CONCRETE, FLOOR_PLANKS];
+const CEILINGS: [usize; 2] = [CEILING_PANELS, CEILING_ROCK];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GeneratorKind {
+    Maze,    // Recursive backtracker with a few loops knocked through
+    Dungeon, // Rectangular rooms joined by corridors
+}
+
+impl GeneratorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeneratorKind::Maze => "Maze",
+            GeneratorKind::Dungeon => "Dungeon",
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Room {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Room {
+    fn centre(&self) -> (usize, usize) {
+        (self.x + self.width / 2, self.y + self.height / 2)
+    }
+
+    // Leaves a one cell gap so rooms never merge into each other
+    fn overlaps(&self, other: &Room) -> bool {
+        self.x <= other.x + other.width && other.x <= self.x + self.width &&
+        self.y <= other.y + other.height && other.y <= self.y + self.height
+    }
+
+    fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+// Sizes are clamped and rounded up to odd numbers so maze corridors line up with the border
+pub fn generate_level(kind: GeneratorKind, width: usize, height: usize, seed: u64) -> Map {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let width = width.clamp(MIN_SIZE, MAX_SIZE) | 1;
+    let height = height.clamp(MIN_SIZE, MAX_SIZE) | 1;
+
+    let mut map = Map::new(width, height);
+    map.name = format!("{} {}", kind.name(), seed);
+    map.music = MUSIC_TRACKS.choose(&mut rng).unwrap_or(&"dark").to_string();
+    let mut palette = [WALL_BRICK, WALL_STONE, WALL_WOOD, WALL_METAL];
+    palette.shuffle(&mut rng);
+    map.palette = palette;
+    for row in &mut map.data {
+        row.fill(CellType::Wall1);
+    }
+
+    let (rooms, spawn) = match kind {
+        GeneratorKind::Maze => (Vec::new(), carve_maze(&mut map, &mut rng)),
+        GeneratorKind::Dungeon => {
+            let rooms = carve_dungeon(&mut map, &mut rng);
+            let spawn = rooms[0].centre();
+            (rooms, spawn)
+        },
+    };
+
+    // The exit goes as far from the spawn as the layout allows
+    let distances = flood_distances(&map, spawn);
+    let exit = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| distances[y][x].is_some())
+        .max_by_key(|&(x, y)| distances[y][x])
+        .unwrap_or(spawn);
+    map.data[exit.1][exit.0] = CellType::Exit;
+
+    map.spawn_x = spawn.0 as f64 + 0.5;
+    map.spawn_y = spawn.1 as f64 + 0.5;
+    map.spawn_angle = facing_open_cell(&map, spawn);
+
+    // Hazards stay off the shortest route so every level can be finished unharmed
+    let mut protected = vec![spawn, exit];
+    if let Some(path) = find_path(&map, spawn, exit, &PathOptions::walking(Connectivity::Four)) {
+        protected.extend(path.cells);
+    }
+    match kind {
+        GeneratorKind::Maze => place_maze_hazards(&mut map, &protected, &mut rng),
+        GeneratorKind::Dungeon => place_room_hazards(&mut map, &rooms[1..], &protected, &mut rng),
+    }
+
+    paint_walls(&mut map, &mut rng);
+    let floor = *FLOORS.choose(&mut rng).unwrap_or(&FLOOR_TILES);
+    let ceiling = *CEILINGS.choose(&mut rng).unwrap_or(&CEILING_PANELS);
+    for y in 0..height {
+        for x in 0..width {
+            map.floor_data[y][x] = if map.is_hazard(x, y) { FLOOR_HAZARD } else { floor };
+            map.ceiling_data[y][x] = ceiling;
+        }
+    }
+
+    if kind == GeneratorKind::Dungeon {
+        furnish_rooms(&mut map, &rooms, &mut rng);
+    }
+
+    map.init_doors();
+    debug_assert!(validate_level(&map).is_ok(), "generated level {} cannot be finished", map.name);
+    map
+}
+
+// Recursive backtracker on the odd cells, returns the spawn cell
+fn carve_maze(map: &mut Map, rng: &mut StdRng) -> (usize, usize) {
+    let start = (1, 1);
+    let mut stack = vec![start];
+    map.data[start.1][start.0] = CellType::Empty;
+
+    while let Some(&(x, y)) = stack.last() {
+        let mut options = Vec::new();
+        if x >= 3 && map.data[y][x - 2] != CellType::Empty {
+            options.push((x - 2, y));
+        }
+        if x + 2 < map.width - 1 && map.data[y][x + 2] != CellType::Empty {
+            options.push((x + 2, y));
+        }
+        if y >= 3 && map.data[y - 2][x] != CellType::Empty {
+            options.push((x, y - 2));
+        }
+        if y + 2 < map.height - 1 && map.data[y + 2][x] != CellType::Empty {
+            options.push((x, y + 2));
+        }
+
+        match options.choose(rng) {
+            Some(&(next_x, next_y)) => {
+                map.data[(y + next_y) / 2][(x + next_x) / 2] = CellType::Empty;
+                map.data[next_y][next_x] = CellType::Empty;
+                stack.push((next_x, next_y));
+            },
+            None => {
+                stack.pop();
+            },
+        }
+    }
+
+    // Knock out some walls between corridors so there is more than one way around
+    let loops = map.width * map.height / 40;
+    for _ in 0..loops {
+        let x = rng.gen_range(1..map.width - 1);
+        let y = rng.gen_range(1..map.height - 1);
+        let horizontal = map.data[y][x - 1] == CellType::Empty && map.data[y][x + 1] == CellType::Empty;
+        let vertical = map.data[y - 1][x] == CellType::Empty && map.data[y + 1][x] == CellType::Empty;
+        if horizontal != vertical {
+            map.data[y][x] = CellType::Empty;
+        }
+    }
+
+    start
+}
+
+// Places rooms that do not touch and joins each one to the previous with an L-shaped corridor
+fn carve_dungeon(map: &mut Map, rng: &mut StdRng) -> Vec<Room> {
+    let mut rooms: Vec<Room> = Vec::new();
+    let max_room = (map.width.min(map.height) / 3).clamp(3, 8);
+    let attempts = map.width * map.height / 8;
+
+    for _ in 0..attempts {
+        let width = rng.gen_range(3..=max_room);
+        let height = rng.gen_range(3..=max_room);
+        if width + 2 > map.width || height + 2 > map.height {
+            continue;
+        }
+        let room = Room {
+            x: rng.gen_range(1..=map.width - width - 1),
+            y: rng.gen_range(1..=map.height - height - 1),
+            width,
+            height,
+        };
+        if rooms.iter().any(|other| room.overlaps(other)) {
+            continue;
+        }
+
+        for y in room.y..room.y + room.height {
+            for x in room.x..room.x + room.width {
+                map.data[y][x] = CellType::Empty;
+            }
+        }
+        if let Some(previous) = rooms.last() {
+            let (from, to) = (previous.centre(), room.centre());
+            if rng.gen_bool(0.5) {
+                carve_corridor(map, from, (to.0, from.1));
+                carve_corridor(map, (to.0, from.1), to);
+            } else {
+                carve_corridor(map, from, (from.0, to.1));
+                carve_corridor(map, (from.0, to.1), to);
+            }
+        }
+        rooms.push(room);
+    }
+
+    // Very small maps may not fit two rooms, fall back to a single hall
+    if rooms.is_empty() {
+        let room = Room { x: 1, y: 1, width: map.width - 2, height: map.height - 2 };
+        for row in &mut map.data[1..map.height - 1] {
+            row[1..map.width - 1].fill(CellType::Empty);
+        }
+        rooms.push(room);
+    }
+    rooms
+}
+
+// Straight corridor, the caller keeps it horizontal or vertical
+fn carve_corridor(map: &mut Map, from: (usize, usize), to: (usize, usize)) {
+    for y in from.1.min(to.1)..=from.1.max(to.1) {
+        for x in from.0.min(to.0)..=from.0.max(to.0) {
+            map.data[y][x] = CellType::Empty;
+        }
+    }
+}
+
+// Breadth-first step counts from `from` to every open cell
+fn flood_distances(map: &Map, from: (usize, usize)) -> Vec<Vec<Option<usize>>> {
+    let mut distances = vec![vec![None; map.width]; map.height];
+    let mut queue = VecDeque::new();
+    distances[from.1][from.0] = Some(0);
+    queue.push_back(from);
+
+    while let Some((x, y)) = queue.pop_front() {
+        let distance = distances[y][x].unwrap_or(0);
+        for (next_x, next_y) in [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)] {
+            if map.get_cell(next_x, next_y) == CellType::Empty && distances[next_y][next_x].is_none() {
+                distances[next_y][next_x] = Some(distance + 1);
+                queue.push_back((next_x, next_y));
+            }
+        }
+    }
+    distances
+}
+
+// Looks down the first open neighbour so the player does not start facing a wall
+fn facing_open_cell(map: &Map, (x, y): (usize, usize)) -> f64 {
+    let directions: [((usize, usize), f64); 4] = [((x + 1, y), 0.0), ((x, y + 1), 90.0), ((x - 1, y), 180.0), ((x, y - 1), 270.0)];
+    for ((cell_x, cell_y), degrees) in directions {
+        if !map.is_wall(cell_x, cell_y) {
+            return degrees.to_radians();
+        }
+    }
+    0.0
+}
+
+// Single hazard cells scattered through the corridors
+fn place_maze_hazards(map: &mut Map, protected: &[(usize, usize)], rng: &mut StdRng) {
+    let count = map.width * map.height / 30;
+    for _ in 0..count {
+        let x = rng.gen_range(1..map.width - 1);
+        let y = rng.gen_range(1..map.height - 1);
+        if map.data[y][x] == CellType::Empty && !protected.contains(&(x, y)) {
+            map.data[y][x] = CellType::Hazard;
+        }
+    }
+}
+
+// A pool in roughly half the rooms, never in the spawn room
+fn place_room_hazards(map: &mut Map, rooms: &[Room], protected: &[(usize, usize)], rng: &mut StdRng) {
+    for room in rooms {
+        if !rng.gen_bool(0.5) {
+            continue;
+        }
+        let size = rng.gen_range(1..=2);
+        let pool_x = rng.gen_range(room.x..room.x + room.width);
+        let pool_y = rng.gen_range(room.y..room.y + room.height);
+        for y in pool_y..pool_y + size {
+            for x in pool_x..pool_x + size {
+                if room.contains(x, y) && map.data[y][x] == CellType::Empty && !protected.contains(&(x, y)) {
+                    map.data[y][x] = CellType::Hazard;
+                }
+            }
+        }
+    }
+}
+
+// Gives each block of the map its own wall type
+fn paint_walls(map: &mut Map, rng: &mut StdRng) {
+    let regions_x = map.width / REGION_SIZE + 1;
+    let regions_y = map.height / REGION_SIZE + 1;
+    let regions: Vec<CellType> = (0..regions_x * regions_y).map(|_| *WALL_TYPES.choose(rng).unwrap_or(&CellType::Wall1)).collect();
+
+    for y in 0..map.height {
+        for x in 0..map.width {
+            if map.is_wall(x, y) {
+                map.data[y][x] = regions[(y / REGION_SIZE) * regions_x + x / REGION_SIZE];
+            }
+        }
+    }
+}
+
+// Torches in room corners and a guard patrolling some of the rooms past the first
+fn furnish_rooms(map: &mut Map, rooms: &[Room], rng: &mut StdRng) {
+    for (i, room) in rooms.iter().enumerate() {
+        let left = room.x as f64 + 0.5;
+        let top = room.y as f64 + 0.5;
+        let right = (room.x + room.width - 1) as f64 + 0.5;
+        let bottom = (room.y + room.height - 1) as f64 + 0.5;
+        for &(x, y) in [(left, top), (right, bottom)].iter() {
+            if let Some(torch) = sprite_by_name("torch", x, y) {
+                map.sprites.push(torch);
+            }
+        }
+
+        if i > 0 && rng.gen_bool(0.4) {
+            let (x, y) = room.centre();
+            if map.data[y][x] == CellType::Empty && !map.is_hazard(room.x, room.y + room.height - 1) {
+                let waypoint = (left, bottom);
+                if let Some(guard) = enemy_by_name("guard", x as f64 + 0.5, y as f64 + 0.5, vec![waypoint]) {
+                    map.enemies.push(guard);
+                }
+            }
+        }
+    }
+}
+
+// Successive levels of one endless run, alternating mazes and dungeons that grow with depth
+#[derive(Clone, Copy)]
+pub struct EndlessRun {
+    pub seed: u64,
+    pub depth: u32,
+}
+
+impl EndlessRun {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, depth: 0 }
+    }
+
+    pub fn generate(&self) -> Map {
+        let kind = if self.depth.is_multiple_of(2) { GeneratorKind::Maze } else { GeneratorKind::Dungeon };
+        let size = 15 + 2 * self.depth as usize;
+        let seed = self.seed.wrapping_add(self.depth as u64);
+        let mut map = generate_level(kind, size + 4, size, seed);
+        map.name = format!("Endless {} - {} {}", self.depth + 1, kind.name(), seed);
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Everything the generator decides, in a form that can be compared
+    fn fingerprint(map: &Map) -> String {
+        let cells: String = map.data.iter().flatten().map(|&cell| char::from(b'0' + cell as u8)).collect();
+        let sprites: Vec<(f64, f64)> = map.sprites.iter().map(|sprite| (sprite.x, sprite.y)).collect();
+        let items: Vec<(ItemKind, f64, f64)> = map.items.iter().map(|item| (item.kind, item.x(), item.y())).collect();
+        let enemies: Vec<(f64, f64)> = map.enemies.iter().map(|enemy| (enemy.x, enemy.y)).collect();
+        format!("{} {} {:?} {:?} {:?} {:?} {:?} {:?} {:?} {}", map.name, cells, map.floor_data, map.ceiling_data,
+            (map.spawn_x, map.spawn_y, map.spawn_angle), sprites, items, enemies, map.palette, map.music)
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_map() {
+        for kind in [GeneratorKind::Maze, GeneratorKind::Dungeon] {
+            for seed in [0, 7, u64::MAX] {
+                let first = generate_level(kind, 25, 21, seed);
+                let second = generate_level(kind, 25, 21, seed);
+                assert_eq!(fingerprint(&first), fingerprint(&second), "{} seed {}", kind.name(), seed);
+            }
+            assert_ne!(fingerprint(&generate_level(kind, 25, 21, 1)), fingerprint(&generate_level(kind, 25, 21, 2)));
+        }
+
+        let run = EndlessRun { seed: 42, depth: 3 };
+        assert_eq!(fingerprint(&run.generate()), fingerprint(&EndlessRun { seed: 42, depth: 3 }.generate()));
+    }
+
+    #[test]
+    fn generated_levels_can_be_finished() {
+        for seed in 0..50 {
+            for kind in [GeneratorKind::Maze, GeneratorKind::Dungeon] {
+                let map = generate_level(kind, 15 + seed as usize % 20, 15, seed);
+                assert!(map.validate_spawn().is_ok(), "{} seed {} has a bad spawn", kind.name(), seed);
+                assert!(validate_level(&map).is_ok(), "{} seed {} cannot be finished", kind.name(), seed);
+            }
+        }
+    }
+}
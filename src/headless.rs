@@ -0,0 +1,140 @@
+// Renders a single frame to a PNG without opening a window or a sound device:
+//
+//   raycasting-game --headless [--level N|path.lvl] [--pos X,Y] [--angle DEGREES]
+//                   [--size WxH] [--out frame.png] [--no-hud] [--flashlight] [--difficulty NAME]
+//   raycasting-game --headless --replay run.rpl [--size WxH] [--out frame.png] [--no-hud]
+//
+// --level takes a 1-based index into the level select list or a path to a level file, and
+// --pos/--angle default to the level's spawn. Used for golden-image tests and level thumbnails.
+// --replay plays a recording to its end as fast as possible and renders where it finished.
+use crate::*;
+use std::path::{Path, PathBuf};
+
+pub const HEADLESS_FLAG: &str = "--headless";
+
+pub struct HeadlessOptions {
+    pub level: String,
+    pub position: Option<(f64, f64)>,
+    pub angle: Option<f64>, // Degrees
+    pub width: usize,
+    pub height: usize,
+    pub output: PathBuf,
+    pub show_hud: bool,
+    pub flashlight: bool,
+    pub difficulty: Difficulty,
+    pub replay: Option<PathBuf>,
+}
+
+impl HeadlessOptions {
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Self {
+            level: String::from("1"),
+            position: None,
+            angle: None,
+            width: WIDTH,
+            height: HEIGHT,
+            output: PathBuf::from("frame.png"),
+            show_hud: true,
+            flashlight: false,
+            difficulty: Difficulty::Normal,
+            replay: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                HEADLESS_FLAG => {},
+                "--level" => options.level = value()?.clone(),
+                "--pos" => options.position = Some(parse_pair(value()?, ',')?),
+                "--angle" => {
+                    let angle = value()?;
+                    options.angle = Some(angle.parse().map_err(|_| format!("invalid angle `{}`", angle))?);
+                },
+                "--size" => {
+                    let (width, height) = parse_pair(value()?, 'x')?;
+                    if width < 1.0 || height < 1.0 {
+                        return Err(String::from("size must be at least 1x1"));
+                    }
+                    options.width = width as usize;
+                    options.height = height as usize;
+                },
+                "--out" => options.output = PathBuf::from(value()?),
+                "--no-hud" => options.show_hud = false,
+                "--flashlight" => options.flashlight = true,
+                "--difficulty" => {
+                    let name = value()?;
+                    options.difficulty = Difficulty::from_name(name).ok_or_else(|| format!("unknown difficulty `{}`", name))?;
+                },
+                REPLAY_FLAG => options.replay = Some(PathBuf::from(value()?)),
+                _ => return Err(format!("unknown option `{}`", arg)),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// Returns the path of the written image
+pub fn run_headless(args: &[String]) -> Result<PathBuf, String> {
+    let options = HeadlessOptions::parse(args)?;
+    let mut game = match &options.replay {
+        Some(path) => play_replay(path)?,
+        None => start_level(&options)?,
+    };
+    game.show_hud = options.show_hud;
+
+    let (width, height) = (options.width, options.height);
+    let buffer = game.render(width, height);
+    let image = image::RgbImage::from_fn(width as u32, height as u32, |x, y| {
+        let pixel = buffer[y as usize * width + x as usize];
+        image::Rgb([(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8])
+    });
+    image.save(&options.output).map_err(|e| format!("{}: {}", options.output.display(), e))?;
+    Ok(options.output)
+}
+
+fn start_level(options: &HeadlessOptions) -> Result<Game, String> {
+    // The player's files must not change reference images, and are never written
+    let mut game = Game::with_defaults(AudioSystem::silent());
+
+    let map = match options.level.parse::<usize>() {
+        Ok(index) if index >= 1 => game.levels.load_map(index - 1).map_err(|e| e.to_string())?,
+        _ => load_level(Path::new(&options.level)).map_err(|e| e.to_string())?,
+    };
+    game.difficulty = options.difficulty;
+    game.begin_level(map);
+    if game.state != GameState::Playing {
+        return Err(game.ui.level_error.clone().unwrap_or_else(|| String::from("level failed to start")));
+    }
+
+    if let Some((x, y)) = options.position {
+        game.player.x = x;
+        game.player.y = y;
+    }
+    if let Some(degrees) = options.angle {
+        game.player.angle = degrees.to_radians();
+    }
+    game.effects.flashlight_enabled = options.flashlight;
+    Ok(game)
+}
+
+fn play_replay(path: &Path) -> Result<Game, String> {
+    let mut playback = Playback::new(Replay::load(path)?);
+    let mut game = playback.replay.new_game(AudioSystem::silent());
+
+    let mut input = InputState::new();
+    while playback.next_tick(&mut input) && !game.quit_requested {
+        game.update(TICK, &input);
+    }
+    match playback.verify(&game) {
+        Ok(()) => println!("Replayed {} ticks, the game matches the recording", playback.tick),
+        Err(e) => println!("Replayed {} ticks, but {}", playback.tick, e),
+    }
+    Ok(game)
+}
+
+fn parse_pair(text: &str, separator: char) -> Result<(f64, f64), String> {
+    let invalid = || format!("invalid value `{}`, expected two numbers separated by `{}`", text, separator);
+    let (first, second) = text.split_once(separator).ok_or_else(invalid)?;
+    Ok((first.trim().parse().map_err(|_| invalid())?, second.trim().parse().map_err(|_| invalid())?))
+}
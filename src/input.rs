@@ -0,0 +1,289 @@
+// Logical actions and their per-frame state. Game logic only sees actions, so the window,
+// scripted input or a replay can all drive it.
+use crate::*;
+use minifb::{Key, Window};
+use std::collections::HashSet;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    Use,
+    Flashlight,
+    Confirm, // Leave the splash screen, continue after a level
+    Restart,
+    Menu,
+    Endless,
+    Controls,
+    Options,
+    SelectLevel(usize), // 0-based level on the level select screen
+    // Menu navigation, kept on fixed keys so a bad binding can always be undone
+    MenuUp,
+    MenuDown,
+    MenuLeft,
+    MenuRight,
+    MenuSelect,
+    Clear,
+    Back,
+}
+
+// Actions in the same group are never active at the same time, so only they can conflict
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ActionGroup {
+    Gameplay,
+    Menu,
+    Fixed,
+}
+
+// Level slots reachable from the number keys
+pub const LEVEL_SLOTS: usize = 9;
+
+impl Action {
+    pub fn group(self) -> ActionGroup {
+        match self {
+            Action::MoveForward | Action::MoveBackward | Action::StrafeLeft | Action::StrafeRight
+            | Action::Use | Action::Flashlight => ActionGroup::Gameplay,
+            Action::Confirm | Action::Restart | Action::Menu | Action::Endless | Action::Controls
+            | Action::Options | Action::SelectLevel(_) => ActionGroup::Menu,
+            Action::MenuUp | Action::MenuDown | Action::MenuLeft | Action::MenuRight
+            | Action::MenuSelect | Action::Clear | Action::Back => ActionGroup::Fixed,
+        }
+    }
+
+    // Name used in the controls file
+    pub fn name(self) -> String {
+        let name = match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::Use => "use",
+            Action::Flashlight => "flashlight",
+            Action::Confirm => "confirm",
+            Action::Restart => "restart",
+            Action::Menu => "menu",
+            Action::Endless => "endless",
+            Action::Controls => "controls",
+            Action::Options => "options",
+            Action::SelectLevel(slot) => return format!("level_{}", slot + 1),
+            Action::MenuUp => "menu_up",
+            Action::MenuDown => "menu_down",
+            Action::MenuLeft => "menu_left",
+            Action::MenuRight => "menu_right",
+            Action::MenuSelect => "menu_select",
+            Action::Clear => "clear",
+            Action::Back => "back",
+        };
+        name.to_string()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        all_actions().find(|action| action.name() == name)
+    }
+
+    // Shown in the controls menu
+    pub fn label(self) -> String {
+        let label = match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBackward => "Move backward",
+            Action::StrafeLeft => "Strafe left",
+            Action::StrafeRight => "Strafe right",
+            Action::Use => "Use",
+            Action::Flashlight => "Flashlight",
+            Action::Confirm => "Continue",
+            Action::Restart => "Restart level",
+            Action::Menu => "Back to menu",
+            Action::Endless => "Endless mode",
+            Action::Controls => "Controls menu",
+            Action::Options => "Options menu",
+            Action::SelectLevel(slot) => return format!("Level {}", slot + 1),
+            Action::MenuUp => "Menu up",
+            Action::MenuDown => "Menu down",
+            Action::MenuLeft => "Menu left",
+            Action::MenuRight => "Menu right",
+            Action::MenuSelect => "Menu select",
+            Action::Clear => "Clear",
+            Action::Back => "Back",
+        };
+        label.to_string()
+    }
+}
+
+#[derive(Default)]
+pub struct InputState {
+    held: HashSet<Action>,
+    previous: HashSet<Action>,
+    pub keys_pressed: Vec<Key>,  // Raw keys that went down this frame, for rebinding
+    pub mouse_delta: (f64, f64), // Already scaled by the mouse sensitivity
+    pub focus_lost: bool,        // The window stopped being the active one
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Starts a new frame with the actions held right now
+    pub fn update(&mut self, held: impl IntoIterator<Item = Action>, keys_pressed: Vec<Key>, mouse_delta: (f64, f64)) {
+        self.previous = std::mem::take(&mut self.held);
+        self.held.extend(held);
+        self.keys_pressed = keys_pressed;
+        self.mouse_delta = mouse_delta;
+        self.focus_lost = false;
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+
+    // Went down this frame
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.held.contains(&action) && !self.previous.contains(&action)
+    }
+}
+
+// Window input gathered between two simulation ticks. Several frames can pass before a tick, or
+// several ticks run in one frame, so taps and mouse movement are kept until a tick takes them.
+#[derive(Default)]
+pub struct PendingInput {
+    held: Vec<Action>,        // Held in the latest frame
+    tapped: HashSet<Action>,  // Held in any frame since the last tick
+    keys_pressed: Vec<Key>,
+    mouse_delta: (f64, f64),
+    focus_lost: bool,
+}
+
+impl PendingInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_frame(&mut self, held: Vec<Action>, keys_pressed: Vec<Key>, mouse_delta: (f64, f64)) {
+        self.tapped.extend(held.iter().copied());
+        self.held = held;
+        self.keys_pressed.extend(keys_pressed);
+        self.mouse_delta.0 += mouse_delta.0;
+        self.mouse_delta.1 += mouse_delta.1;
+    }
+
+    pub fn lose_focus(&mut self) {
+        self.focus_lost = true;
+    }
+
+    // Starts the next tick with everything gathered; further ticks in the same frame only see
+    // the actions still held
+    pub fn apply(&mut self, input: &mut InputState) {
+        let held: Vec<Action> = self.held.iter().copied().chain(self.tapped.drain()).collect();
+        input.update(held, std::mem::take(&mut self.keys_pressed), std::mem::take(&mut self.mouse_delta));
+        input.focus_lost = std::mem::take(&mut self.focus_lost);
+    }
+}
+
+// Keys of the actions that cannot be rebound
+pub fn fixed_keys(action: Action) -> &'static [Key] {
+    match action {
+        Action::MenuUp => &[Key::Up],
+        Action::MenuDown => &[Key::Down],
+        Action::MenuLeft => &[Key::Left],
+        Action::MenuRight => &[Key::Right],
+        Action::MenuSelect => &[Key::Enter],
+        Action::Clear => &[Key::Backspace, Key::Delete],
+        Action::Back => &[Key::Escape],
+        _ => &[],
+    }
+}
+
+pub fn all_actions() -> impl Iterator<Item = Action> {
+    [
+        Action::MoveForward, Action::MoveBackward, Action::StrafeLeft, Action::StrafeRight,
+        Action::Use, Action::Flashlight, Action::Confirm, Action::Restart, Action::Menu, Action::Endless,
+        Action::Controls, Action::Options,
+    ].into_iter()
+        .chain((0..LEVEL_SLOTS).map(Action::SelectLevel))
+        .chain([
+            Action::MenuUp, Action::MenuDown, Action::MenuLeft, Action::MenuRight,
+            Action::MenuSelect, Action::Clear, Action::Back,
+        ])
+}
+
+// Actions whose keys are down in the window
+pub fn window_actions(window: &Window, bindings: &KeyBindings) -> Vec<Action> {
+    all_actions()
+        .filter(|&action| {
+            let mut keys = fixed_keys(action).iter().copied().chain(bindings.keys(action));
+            keys.any(|key| window.is_key_down(key))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tick(input: &mut InputState, held: &[Action]) {
+        input.update(held.iter().copied(), Vec::new(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn press_is_reported_once_then_held() {
+        let mut input = InputState::new();
+        tick(&mut input, &[Action::Use]);
+        assert!(input.is_pressed(Action::Use));
+        assert!(input.is_held(Action::Use));
+
+        tick(&mut input, &[Action::Use]);
+        assert!(!input.is_pressed(Action::Use));
+        assert!(input.is_held(Action::Use));
+    }
+
+    #[test]
+    fn release_ends_hold_and_allows_a_new_press() {
+        let mut input = InputState::new();
+        tick(&mut input, &[Action::Use]);
+        tick(&mut input, &[]);
+        assert!(!input.is_held(Action::Use));
+        assert!(!input.is_pressed(Action::Use));
+
+        tick(&mut input, &[Action::Use]);
+        assert!(input.is_pressed(Action::Use));
+    }
+
+    #[test]
+    fn actions_are_independent() {
+        let mut input = InputState::new();
+        tick(&mut input, &[Action::MoveForward]);
+        tick(&mut input, &[Action::MoveForward, Action::Flashlight]);
+        assert!(input.is_pressed(Action::Flashlight));
+        assert!(!input.is_pressed(Action::MoveForward));
+        assert!(input.is_held(Action::MoveForward));
+    }
+
+    #[test]
+    fn tap_between_ticks_is_not_lost() {
+        let mut pending = PendingInput::new();
+        let mut input = InputState::new();
+        pending.add_frame(vec![Action::Flashlight], Vec::new(), (0.0, 0.0));
+        pending.add_frame(Vec::new(), Vec::new(), (0.0, 0.0));
+        pending.apply(&mut input);
+        assert!(input.is_pressed(Action::Flashlight));
+
+        // A second tick in the same frame only sees what is still held
+        pending.apply(&mut input);
+        assert!(!input.is_held(Action::Flashlight));
+    }
+
+    #[test]
+    fn mouse_movement_adds_up_until_a_tick_takes_it() {
+        let mut pending = PendingInput::new();
+        let mut input = InputState::new();
+        pending.add_frame(Vec::new(), Vec::new(), (0.5, 0.0));
+        pending.add_frame(Vec::new(), Vec::new(), (0.25, 0.1));
+        pending.apply(&mut input);
+        assert_eq!(input.mouse_delta, (0.75, 0.1));
+
+        pending.apply(&mut input);
+        assert_eq!(input.mouse_delta, (0.0, 0.0));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::sprites::*;
+use crate::textures::*;
+
+const PICKUP_RADIUS: f64 = 0.5;
+
+// What the consumables give, and the most the player can carry
+pub const MEDKIT_HEALTH: i32 = 25;
+pub const ARMOR_POINTS: i32 = 50;
+pub const MAX_ARMOR: i32 = 100;
+pub const BATTERY_CHARGE: f64 = 50.0;
+pub const MAX_BATTERY: f64 = 100.0;
+pub const FLASHLIGHT_DRAIN: f64 = 2.0; // Battery used per second with the flashlight on
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KeyColor {
+    Red,
+    Blue,
+    Yellow,
+}
+
+impl KeyColor {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "red" => Some(KeyColor::Red),
+            "blue" => Some(KeyColor::Blue),
+            "yellow" => Some(KeyColor::Yellow),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyColor::Red => "red",
+            KeyColor::Blue => "blue",
+            KeyColor::Yellow => "yellow",
+        }
+    }
+
+    pub fn color(&self) -> u32 {
+        match self {
+            KeyColor::Red => 0xFF3030,
+            KeyColor::Blue => 0x3060FF,
+            KeyColor::Yellow => 0xFFE030,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ItemKind {
+    Key(KeyColor),
+    Medkit,  // Restores MEDKIT_HEALTH, up to the difficulty's starting health
+    Armor,   // Takes a share of the damage instead of health
+    Battery, // Recharges the flashlight
+}
+
+impl ItemKind {
+    // Name in level files, keys also need a color
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "medkit" => Some(ItemKind::Medkit),
+            "armor" => Some(ItemKind::Armor),
+            "battery" => Some(ItemKind::Battery),
+            _ => None,
+        }
+    }
+
+    // Marker on the minimap
+    pub fn color(&self) -> u32 {
+        match self {
+            ItemKind::Key(color) => color.color(),
+            ItemKind::Medkit => 0xF0F0F0,
+            ItemKind::Armor => 0x40E040,
+            ItemKind::Battery => 0xB070FF,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub sprite: Sprite,
+    pub collected: bool,
+}
+
+impl Item {
+    pub fn new(kind: ItemKind, x: f64, y: f64) -> Self {
+        let texture = match kind {
+            ItemKind::Key(KeyColor::Red) => SPRITE_KEY_RED,
+            ItemKind::Key(KeyColor::Blue) => SPRITE_KEY_BLUE,
+            ItemKind::Key(KeyColor::Yellow) => SPRITE_KEY_YELLOW,
+            ItemKind::Medkit => SPRITE_MEDKIT,
+            ItemKind::Armor => SPRITE_ARMOR,
+            ItemKind::Battery => SPRITE_BATTERY,
+        };
+
+        Self {
+            kind,
+            sprite: Sprite::new(x, y, texture),
+            collected: false,
+        }
+    }
+
+    pub fn x(&self) -> f64 {
+        self.sprite.x
+    }
+
+    pub fn y(&self) -> f64 {
+        self.sprite.y
+    }
+
+    pub fn is_touching(&self, x: f64, y: f64) -> bool {
+        let dx = self.x() - x;
+        let dy = self.y() - y;
+        !self.collected && dx * dx + dy * dy < PICKUP_RADIUS * PICKUP_RADIUS
+    }
+}
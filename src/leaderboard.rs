@@ -0,0 +1,208 @@
+// Level scores and the local high-score tables, saved to scores.cfg:
+//
+//   last_name = ALEX
+//   score.Beginner Maze = 5230 42.35 10 hard ALEX     score, time, damage taken, difficulty and name
+//
+// Each level keeps its TABLE_SIZE best scores on every difficulty, highest first. Lines from
+// before difficulties existed have no difficulty and count as normal.
+use crate::*;
+use minifb::Key;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+
+pub const SCORES_FILE: &str = "scores.cfg";
+pub const TABLE_SIZE: usize = 5;
+pub const MAX_NAME_LENGTH: usize = 10;
+
+// Every point of health left is worth HEALTH_POINTS, and finishing is worth TIME_POINTS less
+// TIME_PENALTY for every second taken
+const HEALTH_POINTS: u32 = 10;
+const TIME_POINTS: f64 = 5000.0;
+const TIME_PENALTY: f64 = 10.0;
+
+pub fn level_score(time: f64, health: i32) -> u32 {
+    let time_bonus = (TIME_POINTS - time * TIME_PENALTY).max(0.0) as u32;
+    health.max(0) as u32 * HEALTH_POINTS + time_bonus
+}
+
+// How the last level went, for the success screen
+#[derive(Clone, Default)]
+pub struct LevelResult {
+    pub time: f64,
+    pub health: i32,
+    pub damage_taken: i32,
+    pub score: u32,
+    pub difficulty: Difficulty,
+    pub best_time: Option<f64>, // Level's best before this run, None for generated levels
+    pub new_best: bool,
+    pub rank: Option<usize>,    // Row of the score in the level's table once entered
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: u32,
+    pub time: f64,
+    pub damage_taken: i32,
+    pub difficulty: Difficulty,
+}
+
+#[derive(Clone, Default)]
+pub struct Leaderboard {
+    pub tables: BTreeMap<(String, Difficulty), Vec<ScoreEntry>>, // By level name and difficulty
+    pub last_name: String, // Offered again at the next name entry
+}
+
+impl Leaderboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(Path::new(SCORES_FILE))
+    }
+
+    // Bad lines are reported and skipped
+    pub fn load_from(path: &Path) -> Self {
+        match read_config(path) {
+            Ok(entries) => Self::from_entries(entries, |line, message| eprintln!("Warning: {}:{}: {}", path.display(), line, message)),
+            Err(e) => {
+                eprintln!("Warning: could not read {}: {}", path.display(), e);
+                Self::new()
+            }
+        }
+    }
+
+    // Also used for the scores a replay starts from
+    pub fn from_entries(entries: Vec<ConfigEntry>, mut warn: impl FnMut(usize, String)) -> Self {
+        let mut leaderboard = Self::new();
+        for entry in entries {
+            if entry.name == "last_name" {
+                leaderboard.last_name = clean_name(&entry.value);
+            } else if let Some(level) = entry.name.strip_prefix("score.") {
+                match parse_score(&entry.value) {
+                    Ok(score) => leaderboard.tables.entry((level.to_string(), score.difficulty)).or_default().push(score),
+                    Err(message) => warn(entry.line, message),
+                }
+            } else {
+                warn(entry.line, format!("unknown entry `{}`", entry.name));
+            }
+        }
+        for table in leaderboard.tables.values_mut() {
+            table.sort_by_key(|entry| Reverse(entry.score));
+            table.truncate(TABLE_SIZE);
+        }
+        leaderboard
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to(Path::new(SCORES_FILE))
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        write_config(path, "Best scores per level: score, time, damage taken, difficulty and name.", &self.entries())
+    }
+
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = vec![(String::from("last_name"), self.last_name.clone())];
+        for ((level, _), table) in &self.tables {
+            for score in table {
+                let value = format!("{} {:.2} {} {} {}", score.score, score.time, score.damage_taken, score.difficulty.name(), score.name);
+                entries.push((format!("score.{}", level), value));
+            }
+        }
+        entries
+    }
+
+    pub fn table(&self, level: &str, difficulty: Difficulty) -> &[ScoreEntry] {
+        self.tables.get(&(level.to_string(), difficulty)).map_or(&[], |table| table.as_slice())
+    }
+
+    // Whether the score makes it into the level's table for the difficulty
+    pub fn qualifies(&self, level: &str, difficulty: Difficulty, score: u32) -> bool {
+        let table = self.table(level, difficulty);
+        table.len() < TABLE_SIZE || table.iter().any(|entry| score > entry.score)
+    }
+
+    // Returns the row the score went into, in the table for the entry's difficulty. Ties go below
+    // the scores already there.
+    pub fn insert(&mut self, level: &str, entry: ScoreEntry) -> Option<usize> {
+        self.last_name = entry.name.clone();
+        let table = self.tables.entry((level.to_string(), entry.difficulty)).or_default();
+        let rank = table.iter().position(|other| entry.score > other.score).unwrap_or(table.len());
+        table.insert(rank, entry);
+        table.truncate(TABLE_SIZE);
+        if rank < TABLE_SIZE { Some(rank) } else { None }
+    }
+}
+
+fn parse_score(text: &str) -> Result<ScoreEntry, String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.len() < 4 {
+        return Err(String::from("expected `score time damage difficulty name`"));
+    }
+    let invalid = |word: &str| format!("invalid number `{}`", word);
+    // Names are typed in capitals, so a lowercase difficulty cannot be the start of one
+    let (difficulty, name_start) = match Difficulty::from_name(words[3]) {
+        Some(difficulty) if words.len() > 4 => (difficulty, 4),
+        _ => (Difficulty::Normal, 3),
+    };
+    Ok(ScoreEntry {
+        score: words[0].parse().map_err(|_| invalid(words[0]))?,
+        time: words[1].parse().map_err(|_| invalid(words[1]))?,
+        damage_taken: words[2].parse().map_err(|_| invalid(words[2]))?,
+        difficulty,
+        name: clean_name(&words[name_start..].join(" ")),
+    })
+}
+
+fn clean_name(name: &str) -> String {
+    name.trim().chars().take(MAX_NAME_LENGTH).collect()
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EntryStatus {
+    Editing,
+    Submitted,
+    Cancelled,
+}
+
+// Typing a name for the high-score table
+pub struct NameEntry {
+    pub name: String,
+}
+
+impl NameEntry {
+    pub fn new(name: &str) -> Self {
+        Self { name: clean_name(name) }
+    }
+
+    // Letters, digits and space type, the clear keys erase, Enter saves and Escape skips
+    pub fn update(&mut self, input: &InputState) -> EntryStatus {
+        if input.is_pressed(Action::Back) {
+            return EntryStatus::Cancelled;
+        }
+        if input.is_pressed(Action::MenuSelect) {
+            if self.name.trim().is_empty() {
+                self.name = String::from("PLAYER");
+            }
+            self.name = clean_name(&self.name);
+            return EntryStatus::Submitted;
+        }
+        if input.is_pressed(Action::Clear) {
+            self.name.pop();
+        }
+        for &key in &input.keys_pressed {
+            let label = if key == Key::Space { String::from(" ") } else { key_label(key) };
+            let mut chars = label.chars();
+            if let (Some(c), None) = (chars.next(), chars.next()) {
+                if (c.is_ascii_alphanumeric() || c == ' ') && self.name.len() < MAX_NAME_LENGTH {
+                    self.name.push(c);
+                }
+            }
+        }
+        EntryStatus::Editing
+    }
+}
@@ -0,0 +1,701 @@
+// Level files (levels/*.lvl) are plain text:
+//
+//   # Comment lines start with '#'
+//   name: Beginner Maze
+//   spawn: 1.5 1.5                  player position in cells, or put a P tile in [map]
+//   angle: 0                        facing in degrees, 0 = east, 90 = south
+//   player_radius: 0.2              optional, player collision radius in cells, 0.05 - 0.45
+//   wall_damage: 5 0.5 over_time    optional, damage, cooldown and over_time | on_enter, or none
+//   hazard_damage: 10 0.3 on_enter  optional, as wall_damage, see damage.rs
+//   music: calm                     calm | fortress | labyrinth | dark
+//   floor: tiles                    default floor texture
+//   ceiling: panels                 default ceiling texture
+//   palette: 1=brick 2=stone 3=wood 4=metal
+//
+//   [map]                           one character per cell, rows must be the same length
+//   1111
+//   1051
+//   1111
+//
+//   [floor]                         optional, same size as [map], '.' keeps the default
+//   [ceiling]                       optional, same size as [map], '.' keeps the default
+//
+//   [sprites]                       one "name x y" per line
+//   torch 2.5 1.5
+//
+//   [items]                         one "kind x y" per line, kinds: key red | key blue | key yellow |
+//   key red 2.5 1.5                 medkit | armor | battery
+//   medkit 3.5 1.5
+//
+//   [enemies]                       one "kind x y" per line, extra "x y" pairs are patrol waypoints
+//   guard 2.5 1.5 2.5 5.5
+//
+// Map tiles: 0 empty, 1-4 walls, 5 exit, 6 hazard, D door, R/B/Y door locked by the red, blue
+// or yellow key, P spawn (empty floor). Surface tiles:
+// T tiles, C concrete, W planks, H hazard, P panels, R rock. Every level needs at least
+// one exit, and the spawn must be inside the map on a cell that is not a wall, hazard or exit.
+// Some exit must also be reachable from the spawn, picking up keys for locked doors on the way.
+
+use crate::*;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const LEVEL_DIR: &str = "levels";
+const LEVEL_EXTENSION: &str = "lvl";
+const SPAWN_TILE: char = 'P';
+pub const LEVEL_REPORT_FLAG: &str = "--level-report";
+
+// Copies of the shipped levels, used when the levels directory is missing
+const BUILTIN_LEVELS: [&str; 3] = [
+    include_str!("../levels/01_beginner_maze.lvl"),
+    include_str!("../levels/02_stone_fortress.lvl"),
+    include_str!("../levels/03_metal_labyrinth.lvl"),
+];
+
+#[derive(Debug)]
+pub enum LevelErrorKind {
+    Io(String),
+    ExpectedKeyValue,
+    UnknownKey(String),
+    UnknownSection(String),
+    InvalidValue { key: String, value: String },
+    UnknownTile(char),
+    UnknownSprite(String),
+    UnknownItem(String),
+    UnknownEnemy(String),
+    NonRectangular { expected: usize, found: usize },
+    GridSizeMismatch { section: String, expected: usize, found: usize },
+    DuplicateSpawn,
+    InvalidSpawn(SpawnError),
+    MissingMap,
+    MissingExit,
+    Unfinishable(ValidationError),
+}
+
+#[derive(Debug)]
+pub struct LevelError {
+    pub path: Option<PathBuf>,
+    pub line: usize,   // 1-based, 0 when the error is not tied to a line
+    pub column: usize, // 1-based
+    pub kind: LevelErrorKind,
+}
+
+impl LevelError {
+    fn new(line: usize, column: usize, kind: LevelErrorKind) -> Self {
+        Self { path: None, line, column, kind }
+    }
+}
+
+impl fmt::Display for LevelErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LevelErrorKind::Io(message) => write!(f, "could not read level: {}", message),
+            LevelErrorKind::ExpectedKeyValue => write!(f, "expected `key: value`"),
+            LevelErrorKind::UnknownKey(key) => write!(f, "unknown metadata key `{}`", key),
+            LevelErrorKind::UnknownSection(section) => write!(f, "unknown section `{}`", section),
+            LevelErrorKind::InvalidValue { key, value } => write!(f, "invalid value `{}` for `{}`", value, key),
+            LevelErrorKind::UnknownTile(tile) => write!(f, "unknown tile `{}`", tile),
+            LevelErrorKind::UnknownSprite(name) => write!(f, "unknown sprite `{}`", name),
+            LevelErrorKind::UnknownItem(name) => write!(f, "unknown item `{}`", name),
+            LevelErrorKind::UnknownEnemy(name) => write!(f, "unknown enemy `{}`", name),
+            LevelErrorKind::NonRectangular { expected, found } => {
+                write!(f, "row has {} cells, expected {} like the first row", found, expected)
+            },
+            LevelErrorKind::GridSizeMismatch { section, expected, found } => {
+                write!(f, "[{}] has {} rows, expected {} like [map]", section, found, expected)
+            },
+            LevelErrorKind::DuplicateSpawn => write!(f, "more than one spawn tile (P)"),
+            LevelErrorKind::InvalidSpawn(e) => write!(f, "invalid spawn: {}", e),
+            LevelErrorKind::MissingMap => write!(f, "level has no [map] section"),
+            LevelErrorKind::MissingExit => write!(f, "map has no exit tile (5)"),
+            LevelErrorKind::Unfinishable(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(f, "{}:", path.display())?;
+        }
+        if self.line > 0 {
+            write!(f, "{}:{}: ", self.line, self.column)?;
+        } else if self.path.is_some() {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+#[derive(PartialEq)]
+enum Section {
+    Header,
+    Map,
+    Floor,
+    Ceiling,
+    Sprites,
+    Items,
+    Enemies,
+}
+
+// A grid row together with the line it came from
+struct Row<T> {
+    line: usize,
+    cells: Vec<T>,
+}
+
+pub fn load_level(path: &Path) -> Result<Map, LevelError> {
+    let source = fs::read_to_string(path).map_err(|e| LevelError {
+        path: Some(path.to_path_buf()),
+        line: 0,
+        column: 0,
+        kind: LevelErrorKind::Io(e.to_string()),
+    })?;
+
+    parse_level(&source).map_err(|mut e| {
+        e.path = Some(path.to_path_buf());
+        e
+    })
+}
+
+pub fn parse_level(source: &str) -> Result<Map, LevelError> {
+    let mut section = Section::Header;
+    let mut map_line = 0;
+    let mut last_line = 0;
+
+    let mut name = String::from("Untitled");
+    let mut spawn = (1.5, 1.5);
+    let mut spawn_location = None; // (line, column) of the spawn declaration
+    let mut spawn_tile_found = false;
+    let mut angle = 0.0;
+    let mut player_radius = PLAYER_RADIUS;
+    let mut damage_rules = DamageRules::default();
+    let mut music = String::from("calm");
+    let mut floor = FLOOR_TILES;
+    let mut ceiling = CEILING_PANELS;
+    let mut palette = [WALL_BRICK, WALL_STONE, WALL_WOOD, WALL_METAL];
+
+    let mut rows: Vec<Row<CellType>> = Vec::new();
+    let mut floor_rows: Vec<Row<Option<usize>>> = Vec::new();
+    let mut ceiling_rows: Vec<Row<Option<usize>>> = Vec::new();
+    let mut sprites = Vec::new();
+    let mut items = Vec::new();
+    let mut enemies = Vec::new();
+    let mut locks = Vec::new(); // ((x, y), key) for every locked door tile
+
+    for (index, raw_line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        last_line = line_number;
+        let line = raw_line.trim_end();
+        let trimmed = line.trim_start();
+
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if trimmed.starts_with('[') {
+            let column = line.len() - trimmed.len() + 1;
+            section = match trimmed {
+                "[map]" => Section::Map,
+                "[floor]" => Section::Floor,
+                "[ceiling]" => Section::Ceiling,
+                "[sprites]" => Section::Sprites,
+                "[items]" => Section::Items,
+                "[enemies]" => Section::Enemies,
+                _ => return Err(LevelError::new(line_number, column, LevelErrorKind::UnknownSection(trimmed.to_string()))),
+            };
+            if section == Section::Map {
+                map_line = line_number;
+            }
+            continue;
+        }
+
+        match section {
+            Section::Header => {
+                let (key, raw_value) = match line.split_once(':') {
+                    Some((key, raw_value)) => (key.trim(), raw_value),
+                    None => return Err(LevelError::new(line_number, 1, LevelErrorKind::ExpectedKeyValue)),
+                };
+                let value = raw_value.trim();
+                let value_column = line.len() - raw_value.trim_start().len() + 1;
+                let invalid = || LevelError::new(line_number, value_column, LevelErrorKind::InvalidValue {
+                    key: key.to_string(),
+                    value: value.to_string(),
+                });
+
+                match key {
+                    "name" => name = value.to_string(),
+                    "spawn" => {
+                        spawn = parse_position(&words_with_columns(value, value_column), line_number, key)?;
+                        spawn_location = Some((line_number, value_column));
+                    },
+                    "angle" => {
+                        let degrees: f64 = value.parse().map_err(|_| invalid())?;
+                        angle = degrees.to_radians();
+                    },
+                    "player_radius" => {
+                        // Below half a cell so the player always fits through one-cell corridors
+                        player_radius = value.parse().map_err(|_| invalid())?;
+                        if !(0.05..=0.45).contains(&player_radius) {
+                            return Err(invalid());
+                        }
+                    },
+                    "wall_damage" => damage_rules.wall = DamageRule::parse(value).map_err(|_| invalid())?,
+                    "hazard_damage" => damage_rules.hazard = DamageRule::parse(value).map_err(|_| invalid())?,
+                    "music" => {
+                        if !MUSIC_TRACKS.contains(&value) {
+                            return Err(invalid());
+                        }
+                        music = value.to_string();
+                    },
+                    "floor" => floor = surface_texture_by_name(value).ok_or_else(invalid)?,
+                    "ceiling" => ceiling = surface_texture_by_name(value).ok_or_else(invalid)?,
+                    "palette" => {
+                        for (entry_column, entry) in words_with_columns(value, value_column) {
+                            let invalid_entry = || LevelError::new(line_number, entry_column, LevelErrorKind::InvalidValue {
+                                key: key.to_string(),
+                                value: entry.to_string(),
+                            });
+                            let (wall, texture) = entry.split_once('=').ok_or_else(invalid_entry)?;
+                            let slot = match wall {
+                                "1" => 0,
+                                "2" => 1,
+                                "3" => 2,
+                                "4" => 3,
+                                _ => return Err(invalid_entry()),
+                            };
+                            palette[slot] = wall_texture_by_name(texture).ok_or_else(invalid_entry)?;
+                        }
+                    },
+                    _ => return Err(LevelError::new(line_number, 1, LevelErrorKind::UnknownKey(key.to_string()))),
+                }
+            },
+            Section::Map => {
+                let mut cells = Vec::new();
+                for (column, tile) in line.chars().enumerate() {
+                    // The spawn tile overrides any spawn metadata
+                    if tile == SPAWN_TILE {
+                        if spawn_tile_found {
+                            return Err(LevelError::new(line_number, column + 1, LevelErrorKind::DuplicateSpawn));
+                        }
+                        spawn_tile_found = true;
+                        spawn = (column as f64 + 0.5, rows.len() as f64 + 0.5);
+                        spawn_location = Some((line_number, column + 1));
+                        cells.push(CellType::Empty);
+                        continue;
+                    }
+                    if let Some(color) = door_lock(tile) {
+                        locks.push(((column, rows.len()), color));
+                    }
+                    match CellType::from_tile(tile) {
+                        Some(cell) => cells.push(cell),
+                        None => return Err(LevelError::new(line_number, column + 1, LevelErrorKind::UnknownTile(tile))),
+                    }
+                }
+                check_row_width(&rows, cells.len(), line_number)?;
+                rows.push(Row { line: line_number, cells });
+            },
+            Section::Floor | Section::Ceiling => {
+                let mut cells = Vec::new();
+                for (column, tile) in line.chars().enumerate() {
+                    match surface_tile(tile) {
+                        Some(surface) => cells.push(surface),
+                        None => return Err(LevelError::new(line_number, column + 1, LevelErrorKind::UnknownTile(tile))),
+                    }
+                }
+                let target = if section == Section::Floor { &mut floor_rows } else { &mut ceiling_rows };
+                check_row_width(target, cells.len(), line_number)?;
+                target.push(Row { line: line_number, cells });
+            },
+            Section::Sprites => {
+                let words = words_with_columns(line, 1);
+                let (column, sprite_name) = words[0];
+                let (x, y) = parse_position(&words[1..], line_number, "sprite")?;
+                match sprite_by_name(sprite_name, x, y) {
+                    Some(sprite) => sprites.push(sprite),
+                    None => return Err(LevelError::new(line_number, column, LevelErrorKind::UnknownSprite(sprite_name.to_string()))),
+                }
+            },
+            Section::Items => {
+                let words = words_with_columns(line, 1);
+                let (column, kind_name) = words[0];
+                let unknown = || LevelError::new(line_number, column, LevelErrorKind::UnknownItem(kind_name.to_string()));
+                // Keys take their color before the position
+                let (kind, position_start) = match kind_name {
+                    "key" => {
+                        let &(color_column, color_name) = words.get(1).ok_or_else(unknown)?;
+                        match KeyColor::from_name(color_name) {
+                            Some(color) => (ItemKind::Key(color), 2),
+                            None => return Err(LevelError::new(line_number, color_column, LevelErrorKind::UnknownItem(format!("key {}", color_name)))),
+                        }
+                    },
+                    _ => (ItemKind::from_name(kind_name).ok_or_else(unknown)?, 1),
+                };
+                let (x, y) = parse_position(&words[position_start..], line_number, "item")?;
+                items.push(Item::new(kind, x, y));
+            },
+            Section::Enemies => {
+                let words = words_with_columns(line, 1);
+                let (column, enemy_name) = words[0];
+                let (x, y) = parse_position(&words[1..words.len().min(3)], line_number, "enemy")?;
+                let mut waypoints = Vec::new();
+                for pair in words.get(3..).unwrap_or_default().chunks(2) {
+                    waypoints.push(parse_position(pair, line_number, "enemy")?);
+                }
+                match enemy_by_name(enemy_name, x, y, waypoints) {
+                    Some(enemy) => enemies.push(enemy),
+                    None => return Err(LevelError::new(line_number, column, LevelErrorKind::UnknownEnemy(enemy_name.to_string()))),
+                }
+            },
+        }
+    }
+
+    if rows.is_empty() {
+        return Err(LevelError::new(last_line + 1, 1, LevelErrorKind::MissingMap));
+    }
+
+    let height = rows.len();
+    let width = rows[0].cells.len();
+    let mut map = Map::new(width, height);
+    map.name = name;
+    map.spawn_x = spawn.0;
+    map.spawn_y = spawn.1;
+    map.spawn_angle = angle;
+    map.player_radius = player_radius;
+    map.damage_rules = damage_rules;
+    map.music = music;
+    map.palette = palette;
+    map.sprites = sprites;
+    map.items = items;
+    map.enemies = enemies;
+
+    for (y, row) in rows.into_iter().enumerate() {
+        for (x, cell) in row.cells.into_iter().enumerate() {
+            map.data[y][x] = cell;
+            map.floor_data[y][x] = if cell == CellType::Hazard { FLOOR_HAZARD } else { floor };
+            map.ceiling_data[y][x] = ceiling;
+        }
+    }
+
+    if !map.data.iter().flatten().any(|&cell| cell == CellType::Exit) {
+        return Err(LevelError::new(map_line, 1, LevelErrorKind::MissingExit));
+    }
+
+    map.init_doors();
+    for ((x, y), color) in locks {
+        if let Some(door) = map.get_door_mut(x, y) {
+            door.lock = Some(color);
+        }
+    }
+
+    if let Err(e) = map.validate_spawn() {
+        let (line, column) = spawn_location.unwrap_or((map_line, 1));
+        return Err(LevelError::new(line, column, LevelErrorKind::InvalidSpawn(e)));
+    }
+
+    apply_surface_grid(&mut map.floor_data, floor_rows, "floor", width, height)?;
+    apply_surface_grid(&mut map.ceiling_data, ceiling_rows, "ceiling", width, height)?;
+
+    if let Err(e) = validate_level(&map) {
+        return Err(LevelError::new(map_line, 1, LevelErrorKind::Unfinishable(e)));
+    }
+
+    Ok(map)
+}
+
+// Locked door tiles and the key they need
+fn door_lock(tile: char) -> Option<KeyColor> {
+    match tile {
+        'R' => Some(KeyColor::Red),
+        'B' => Some(KeyColor::Blue),
+        'Y' => Some(KeyColor::Yellow),
+        _ => None,
+    }
+}
+
+// Splits on whitespace, keeping the 1-based column where each word starts
+fn words_with_columns(text: &str, first_column: usize) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, ch) in text.char_indices().chain(std::iter::once((text.len(), ' '))) {
+        match (ch.is_whitespace(), start) {
+            (false, None) => start = Some(index),
+            (true, Some(word_start)) => {
+                words.push((first_column + word_start, &text[word_start..index]));
+                start = None;
+            },
+            _ => {},
+        }
+    }
+    words
+}
+
+// Parses exactly two words as an "x y" position in cells
+fn parse_position(words: &[(usize, &str)], line_number: usize, key: &str) -> Result<(f64, f64), LevelError> {
+    let mut coords = [0.0; 2];
+    for (i, coord) in coords.iter_mut().enumerate() {
+        let (column, word) = match words.get(i) {
+            Some(&word) => word,
+            None => {
+                let column = words.last().map_or(1, |(column, word)| column + word.len());
+                return Err(LevelError::new(line_number, column, LevelErrorKind::InvalidValue {
+                    key: key.to_string(),
+                    value: String::from("missing coordinate"),
+                }));
+            }
+        };
+        *coord = word.parse().map_err(|_| LevelError::new(line_number, column, LevelErrorKind::InvalidValue {
+            key: key.to_string(),
+            value: word.to_string(),
+        }))?;
+    }
+    if let Some(&(column, word)) = words.get(2) {
+        return Err(LevelError::new(line_number, column, LevelErrorKind::InvalidValue {
+            key: key.to_string(),
+            value: word.to_string(),
+        }));
+    }
+    Ok((coords[0], coords[1]))
+}
+
+fn check_row_width<T>(rows: &[Row<T>], width: usize, line_number: usize) -> Result<(), LevelError> {
+    if let Some(first) = rows.first() {
+        let expected = first.cells.len();
+        if width != expected {
+            return Err(LevelError::new(line_number, width.min(expected) + 1, LevelErrorKind::NonRectangular {
+                expected,
+                found: width,
+            }));
+        }
+    }
+    Ok(())
+}
+
+// '.' keeps the default surface for that cell
+fn surface_tile(tile: char) -> Option<Option<usize>> {
+    match tile {
+        '.' => Some(None),
+        'T' => Some(Some(FLOOR_TILES)),
+        'C' => Some(Some(FLOOR_CONCRETE)),
+        'W' => Some(Some(FLOOR_PLANKS)),
+        'H' => Some(Some(FLOOR_HAZARD)),
+        'P' => Some(Some(CEILING_PANELS)),
+        'R' => Some(Some(CEILING_ROCK)),
+        _ => None,
+    }
+}
+
+fn apply_surface_grid(target: &mut [Vec<usize>], rows: Vec<Row<Option<usize>>>, section: &str, width: usize, height: usize) -> Result<(), LevelError> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+    if rows.len() != height {
+        return Err(LevelError::new(rows[0].line, 1, LevelErrorKind::GridSizeMismatch {
+            section: section.to_string(),
+            expected: height,
+            found: rows.len(),
+        }));
+    }
+    if rows[0].cells.len() != width {
+        return Err(LevelError::new(rows[0].line, width.min(rows[0].cells.len()) + 1, LevelErrorKind::NonRectangular {
+            expected: width,
+            found: rows[0].cells.len(),
+        }));
+    }
+
+    for (y, row) in rows.into_iter().enumerate() {
+        for (x, surface) in row.cells.into_iter().enumerate() {
+            if let Some(surface) = surface {
+                target[y][x] = surface;
+            }
+        }
+    }
+    Ok(())
+}
+
+enum LevelSource {
+    File(PathBuf),
+    Builtin(&'static str),
+}
+
+pub struct LevelEntry {
+    pub name: String,
+    source: LevelSource,
+}
+
+pub struct LevelCatalog {
+    pub levels: Vec<LevelEntry>,
+}
+
+impl LevelCatalog {
+    // Scans the levels directory so new levels show up without recompiling
+    pub fn load() -> Self {
+        let entries = match fs::read_dir(LEVEL_DIR) {
+            Ok(entries) => entries,
+            Err(_) => {
+                println!("Warning: Could not open {}/, using built-in levels", LEVEL_DIR);
+                return Self::builtin();
+            }
+        };
+
+        let mut paths: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == LEVEL_EXTENSION))
+            .collect();
+        paths.sort();
+
+        let mut levels = Vec::new();
+        for path in paths {
+            match load_level(&path) {
+                Ok(map) => levels.push(LevelEntry {
+                    name: map.name,
+                    source: LevelSource::File(path),
+                }),
+                Err(e) => println!("Error: {}", e),
+            }
+        }
+
+        if levels.is_empty() {
+            println!("Warning: No valid levels in {}/, using built-in levels", LEVEL_DIR);
+            return Self::builtin();
+        }
+
+        Self { levels }
+    }
+
+    fn builtin() -> Self {
+        let levels = BUILTIN_LEVELS
+            .iter()
+            .filter_map(|source| parse_level(source).ok().map(|map| LevelEntry {
+                name: map.name,
+                source: LevelSource::Builtin(source),
+            }))
+            .collect();
+
+        Self { levels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    // Files are re-read on every start so edits apply without restarting the game
+    pub fn load_map(&self, index: usize) -> Result<Map, LevelError> {
+        let entry = self.levels.get(index).ok_or_else(|| {
+            LevelError::new(0, 0, LevelErrorKind::Io(format!("no level number {}", index + 1)))
+        })?;
+
+        match &entry.source {
+            LevelSource::File(path) => load_level(path),
+            LevelSource::Builtin(source) => parse_level(source),
+        }
+    }
+
+    // What each level takes to finish with its own damage rules, printed for --level-report
+    pub fn print_reports(&self) {
+        for index in 0..self.len() {
+            let map = match self.load_map(index) {
+                Ok(map) => map,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    continue;
+                }
+            };
+            match validate_level(&map) {
+                Ok(report) => {
+                    let keys: Vec<&str> = report.keys.iter().map(|key| key.name()).collect();
+                    println!("{}: exit in {} steps, {} hazard cells, at least {} hazard damage, keys: {}",
+                        map.name, report.steps, report.hazard_cells, report.min_hazard_damage,
+                        if keys.is_empty() { String::from("none") } else { keys.join(" ") });
+                },
+                Err(e) => println!("{}: {}", map.name, e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRID: &str = "[map]\n11111\n1P051\n11111\n";
+
+    fn level(header: &str) -> Result<Map, LevelError> {
+        parse_level(&format!("name: Test\n{}{}", header, GRID))
+    }
+
+    fn error_at(source: &str) -> LevelError {
+        parse_level(source).err().expect("bad level accepted")
+    }
+
+    #[test]
+    fn unknown_tile_points_at_the_tile() {
+        let error = error_at("name: Test\n[map]\n11111\n1P0X1\n11151\n");
+        assert_eq!((error.line, error.column), (4, 4));
+        assert!(matches!(error.kind, LevelErrorKind::UnknownTile('X')));
+    }
+
+    #[test]
+    fn rows_must_be_the_same_length() {
+        let error = error_at("name: Test\n[map]\n11111\n1P051\n1111\n");
+        assert_eq!((error.line, error.column), (5, 5));
+        assert!(matches!(error.kind, LevelErrorKind::NonRectangular { expected: 5, found: 4 }));
+    }
+
+    #[test]
+    fn missing_exit_points_at_the_map() {
+        let error = error_at("name: Test\n\n[map]\n11111\n1P001\n11111\n");
+        assert_eq!((error.line, error.column), (3, 1));
+        assert!(matches!(error.kind, LevelErrorKind::MissingExit));
+    }
+
+    #[test]
+    fn missing_spawn_falls_back_to_a_spawn_that_must_be_valid() {
+        // No P tile and no spawn key leaves the default spawn at (1.5, 1.5), here a wall
+        let error = error_at("name: Test\n[map]\n11111\n11051\n11111\n");
+        assert_eq!((error.line, error.column), (2, 1));
+        assert!(matches!(error.kind, LevelErrorKind::InvalidSpawn(SpawnError::InWall)));
+    }
+
+    #[test]
+    fn second_spawn_tile_is_rejected() {
+        let error = error_at("name: Test\n[map]\n11111\n1P0P1\n11151\n");
+        assert_eq!((error.line, error.column), (4, 4));
+        assert!(matches!(error.kind, LevelErrorKind::DuplicateSpawn));
+    }
+
+    #[test]
+    fn unknown_music_track_points_at_its_value() {
+        let error = error_at(&format!("name: Test\nmusic:  disco\n{}", GRID));
+        assert_eq!((error.line, error.column), (2, 9));
+        assert!(matches!(&error.kind, LevelErrorKind::InvalidValue { key, value } if key == "music" && value == "disco"));
+        assert_eq!(level("music: dark\n").unwrap().music, "dark");
+    }
+
+    #[test]
+    fn damage_rules_default_when_left_out() {
+        let map = level("").unwrap();
+        assert_eq!(map.damage_rules, DamageRules::default());
+    }
+
+    #[test]
+    fn damage_rules_are_read_from_the_header() {
+        let map = level("wall_damage: none\nhazard_damage: 7 0.2 on_enter\n").unwrap();
+        assert_eq!(map.damage_rules.wall, None);
+        assert_eq!(map.damage_rules.hazard, Some(DamageRule { amount: 7, cooldown: 0.2, mode: DamageMode::OnEnter }));
+    }
+
+    #[test]
+    fn bad_damage_rule_points_at_its_value() {
+        let error = error_at(&format!("name: Test\nhazard_damage: 7 soon\n{}", GRID));
+        assert_eq!((error.line, error.column), (2, 16));
+        assert!(matches!(&error.kind, LevelErrorKind::InvalidValue { key, value } if key == "hazard_damage" && value == "7 soon"));
+
+        let error = error_at(&format!("name: Test\nmusic: calm\nwall_damage:   5 0.5 sideways\n{}", GRID));
+        assert_eq!((error.line, error.column), (3, 16));
+        assert!(matches!(&error.kind, LevelErrorKind::InvalidValue { key, .. } if key == "wall_damage"));
+    }
+}
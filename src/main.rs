@@ -1,23 +1,66 @@
-use minifb::{Key, Window, WindowOptions, MouseMode, CursorStyle};
+use minifb::{Key, KeyRepeat, Window, WindowOptions, MouseMode, CursorStyle};
 use std::time::{Duration, Instant};
+use std::path::PathBuf;
 use std::f64::consts::PI;
 use rand::Rng;
 
 mod game;
 mod player;
+mod collision;
+mod damage;
+mod difficulty;
 mod map;
 mod raycaster;
 mod audio;
 mod ui;
 mod effects;
+mod events;
+mod textures;
+mod sprites;
+mod level;
+mod door;
+mod items;
+mod enemy;
+mod pathfinding;
+mod generator;
+mod headless;
+mod input;
+mod config;
+mod controls;
+mod settings;
+mod save;
+mod timestep;
+mod replay;
+mod leaderboard;
 
 use game::*;
 use player::*;
+use collision::*;
+use damage::*;
+use difficulty::*;
 use map::*;
 use raycaster::*;
 use audio::*;
 use ui::*;
 use effects::*;
+use events::*;
+use textures::*;
+use sprites::*;
+use level::*;
+use door::*;
+use items::*;
+use enemy::*;
+use pathfinding::*;
+use generator::*;
+use headless::*;
+use input::*;
+use config::*;
+use controls::*;
+use settings::*;
+use save::*;
+use timestep::*;
+use replay::*;
+use leaderboard::*;
 
 const WIDTH: usize = 800;
 const HEIGHT: usize = 600;
@@ -25,20 +68,48 @@ const FULLSCREEN_WIDTH: usize = 1600;
 const FULLSCREEN_HEIGHT: usize = 900;
 
 fn main() {
-    let mut window_options = WindowOptions::default();
-    let mut is_fullscreen = false;
-    
-    let mut window = Window::new(
-        "Ray Casting Game - Rust",
-        WIDTH,
-        HEIGHT,
-        window_options,
-    )
-    .unwrap_or_else(|e| {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == HEADLESS_FLAG) {
+        match run_headless(&args) {
+            Ok(path) => println!("Wrote {}", path.display()),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.iter().any(|arg| arg == LEVEL_REPORT_FLAG) {
+        LevelCatalog::load().print_reports();
+        return;
+    }
+
+    // --record FILE keeps every tick's input, --replay FILE plays a recording back
+    let option = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).map(PathBuf::from);
+    let record_path = option(RECORD_FLAG);
+    let mut playback = match option(REPLAY_FLAG) {
+        Some(path) => match Replay::load(&path) {
+            Ok(replay) => Some(Playback::new(replay)),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let mut game = match &playback {
+        Some(playback) => playback.replay.new_game(AudioSystem::new()),
+        None => Game::new(),
+    };
+    let mut recording = record_path.as_ref().map(|_| Replay::start(&game));
+    let mut is_fullscreen = game.settings.fullscreen;
+    let mut window = create_window(is_fullscreen).unwrap_or_else(|e| {
         panic!("{}", e);
     });
-    
-    let mut game = Game::new();
+    let mut input = InputState::new();
+    let mut pending_input = PendingInput::new();
+    let mut timestep = FixedTimestep::new();
     let mut last_time = Instant::now();
     let mut fps_counter = 0;
     let mut fps_timer = Instant::now();
@@ -47,9 +118,9 @@ fn main() {
     let mut mouse_captured = false;
     let mut last_f11_state = false;
 
-    while window.is_open() && !window.is_key_down(Key::Escape) {
+    while window.is_open() && !game.quit_requested {
         let current_time = Instant::now();
-        let delta_time = current_time.duration_since(last_time).as_secs_f64();
+        let frame_time = current_time.duration_since(last_time).as_secs_f64();
         last_time = current_time;
 
         // FPS counter
@@ -60,39 +131,40 @@ fn main() {
             fps_timer = Instant::now();
         }
         
-        // Handle fullscreen toggle with F11
+        // F11 flips the fullscreen setting, the window follows it below
         let f11_pressed = window.is_key_down(Key::F11);
         if f11_pressed && !last_f11_state {
-            is_fullscreen = !is_fullscreen;
-            
-            // Recreate window with new fullscreen state
-            let mut new_options = WindowOptions::default();
-            if is_fullscreen {
-                new_options.borderless = true;
-                new_options.title = false;
-                new_options.resize = false;
-            }
-            
-            // Use large resolution for fullscreen
-            let (new_width, new_height) = if is_fullscreen {
-                (1920, 1080)
-            } else {
-                (WIDTH, HEIGHT)
-            };
-            
-            // Create new window
-            if let Ok(new_window) = Window::new(
-                "Ray Casting Game - Rust",
-                new_width,
-                new_height,
-                new_options,
-            ) {
-                window = new_window;
-                println!("Toggled fullscreen: {}", is_fullscreen);
+            game.settings.fullscreen = !game.settings.fullscreen;
+            // Replays leave the settings file alone
+            if game.persist {
+                if let Err(e) = game.settings.save() {
+                    eprintln!("Warning: could not save {}: {}", SETTINGS_FILE, e);
+                }
             }
         }
         last_f11_state = f11_pressed;
 
+        // Recreate the window when fullscreen changed, from F11 or the options menu
+        if game.settings.fullscreen != is_fullscreen {
+            match create_window(game.settings.fullscreen) {
+                Ok(new_window) => {
+                    window = new_window;
+                    is_fullscreen = game.settings.fullscreen;
+                    cursor_hidden = false; // New windows start with a visible cursor
+                    println!("Toggled fullscreen: {}", is_fullscreen);
+                }
+                Err(e) => {
+                    eprintln!("Warning: could not change fullscreen: {}", e);
+                    game.settings.fullscreen = is_fullscreen;
+                }
+            }
+        }
+
+        // Switching to another window pauses the level, on the next tick
+        if !window.is_active() {
+            pending_input.lose_focus();
+        }
+
         // Handle cursor visibility and mouse capture based on game state
         if matches!(game.state, GameState::Playing) {
             // Hide cursor and capture mouse during gameplay
@@ -118,10 +190,9 @@ fn main() {
             let mouse_delta_y = current_mouse_pos.1 - last_mouse_pos.1;
             
             // Apply sensitivity to mouse movement
-            let sensitivity = 0.00005;
-            let mouse_input = (mouse_delta_x * sensitivity, mouse_delta_y * sensitivity);
+            let mouse_input = game.settings.mouse_look(mouse_delta_x as f64, mouse_delta_y as f64);
             
-            game.update(delta_time, &window, mouse_input);
+            pending_input.add_frame(window_actions(&window, &game.bindings), window.get_keys_pressed(KeyRepeat::No), mouse_input);
             
             // Update last mouse position
             last_mouse_pos = current_mouse_pos;
@@ -151,9 +222,32 @@ fn main() {
             
             // Normal mouse handling for menus
             let mouse_pos = window.get_mouse_pos(MouseMode::Pass).unwrap_or(last_mouse_pos);
-            game.update(delta_time, &window, (0.0, 0.0));
+            pending_input.add_frame(window_actions(&window, &game.bindings), window.get_keys_pressed(KeyRepeat::No), (0.0, 0.0));
             last_mouse_pos = mouse_pos;
         }
+
+        // Run the simulation at its fixed rate, then draw between its last two ticks
+        for _ in 0..timestep.advance(frame_time) {
+            if game.quit_requested {
+                break;
+            }
+            pending_input.apply(&mut input);
+            // A replay replaces the window input until it runs out, then the player takes over
+            let replay_over = playback.as_mut().is_some_and(|playback| !playback.next_tick(&mut input));
+            if replay_over {
+                if let Some(playback) = playback.take() {
+                    match playback.verify(&game) {
+                        Ok(()) => println!("Replay finished after {} ticks, the game matches the recording", playback.tick),
+                        Err(e) => println!("Replay finished after {} ticks, but {}", playback.tick, e),
+                    }
+                }
+            }
+            if let Some(recording) = &mut recording {
+                recording.record(&input);
+            }
+            game.update(TICK, &input);
+        }
+        game.interpolation = timestep.alpha();
         
         // Get actual window dimensions for rendering
         let (render_width, render_height) = if is_fullscreen {
@@ -167,4 +261,28 @@ fn main() {
         
         window.update_with_buffer(&buffer, render_width, render_height).unwrap();
     }
-}
\ No newline at end of file
+
+    if let (Some(recording), Some(path)) = (&mut recording, &record_path) {
+        match recording.save_to(path, &game) {
+            Ok(()) => println!("Recorded {} ticks to {}", recording.ticks.len(), path.display()),
+            Err(e) => eprintln!("Warning: could not write {}: {}", path.display(), e),
+        }
+    }
+
+    // Closing the window mid-level keeps the level to continue next time
+    game.suspend_level();
+}
+
+fn create_window(fullscreen: bool) -> minifb::Result<Window> {
+    let mut options = WindowOptions::default();
+    let (width, height) = if fullscreen {
+        options.borderless = true;
+        options.title = false;
+        options.resize = false;
+        // Use large resolution for fullscreen
+        (1920, 1080)
+    } else {
+        (WIDTH, HEIGHT)
+    };
+    Window::new("Ray Casting Game - Rust", width, height, options)
+}
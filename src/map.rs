@@ -1,132 +1,101 @@
+use crate::damage::*;
+use crate::door::*;
+use crate::enemy::*;
+use crate::items::*;
+use crate::player::PLAYER_RADIUS;
+use crate::sprites::*;
+use crate::textures::*;
+use std::collections::HashMap;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum CellType {
     Empty = 0,
-    Wall1 = 1,  // Red brick wall
-    Wall2 = 2,  // Blue stone wall
-    Wall3 = 3,  // Green wood wall
-    Wall4 = 4,  // Yellow metal wall
+    Wall1 = 1,  // Brick wall by default
+    Wall2 = 2,  // Stone wall by default
+    Wall3 = 3,  // Wood wall by default
+    Wall4 = 4,  // Metal wall by default
     Exit = 5,   // Exit point
     Hazard = 6, // Damage zone
+    Door = 7,   // Sliding door, state lives in Map::doors
+}
+
+impl CellType {
+    // Tile character used in level files
+    pub fn from_tile(tile: char) -> Option<Self> {
+        match tile {
+            '0' => Some(CellType::Empty),
+            '1' => Some(CellType::Wall1),
+            '2' => Some(CellType::Wall2),
+            '3' => Some(CellType::Wall3),
+            '4' => Some(CellType::Wall4),
+            '5' => Some(CellType::Exit),
+            '6' => Some(CellType::Hazard),
+            'D' | 'R' | 'B' | 'Y' => Some(CellType::Door),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpawnError {
+    OutOfBounds,
+    InWall,
+    OnHazard,
+    OnExit,
+}
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SpawnError::OutOfBounds => write!(f, "spawn is outside the map"),
+            SpawnError::InWall => write!(f, "spawn is inside a wall"),
+            SpawnError::OnHazard => write!(f, "spawn is on a hazard"),
+            SpawnError::OnExit => write!(f, "spawn is on the exit"),
+        }
+    }
 }
 
 pub struct Map {
+    pub name: String,
     pub width: usize,
     pub height: usize,
     pub data: Vec<Vec<CellType>>,
+    pub floor_data: Vec<Vec<usize>>,   // Floor texture id per cell
+    pub ceiling_data: Vec<Vec<usize>>, // Ceiling texture id per cell
+    pub sprites: Vec<Sprite>,          // Decorations placed when the level starts
+    pub doors: HashMap<(usize, usize), Door>,
+    pub items: Vec<Item>, // Pickups placed when the level starts
+    pub enemies: Vec<Enemy>, // Enemies at their spawn points
+    pub spawn_x: f64,
+    pub spawn_y: f64,
+    pub spawn_angle: f64, // Radians
+    pub player_radius: f64, // Cells
+    pub damage_rules: DamageRules,
+    pub music: String,
+    pub palette: [usize; 4], // Wall texture id for Wall1..Wall4
 }
 
 impl Map {
-    pub fn new(level: usize) -> Self {
-        match level {
-            0 => Self::create_level_1(),
-            1 => Self::create_level_2(),
-            2 => Self::create_level_3(),
-            _ => Self::create_level_1(),
-        }
-    }
-
-    fn create_level_1() -> Self {
-        let data = vec![
-            vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 0, 1],
-            vec![1, 0, 2, 0, 0, 0, 0, 3, 3, 0, 0, 0, 0, 2, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 3, 3, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 4, 0, 0, 0, 0, 4, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 4, 0, 0, 0, 0, 4, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 6, 6, 0, 0, 0, 0, 0, 0, 6, 6, 0, 0, 1],
-            vec![1, 0, 0, 6, 6, 0, 0, 0, 0, 0, 0, 6, 6, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 1],
-            vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-        ];
-        
-        Self {
-            width: 16,
-            height: 13,
-            data: data.into_iter().map(|row| {
-                row.into_iter().map(|cell| match cell {
-                    0 => CellType::Empty,
-                    1 => CellType::Wall1,
-                    2 => CellType::Wall2,
-                    3 => CellType::Wall3,
-                    4 => CellType::Wall4,
-                    5 => CellType::Exit,
-                    6 => CellType::Hazard,
-                    _ => CellType::Empty,
-                }).collect()
-            }).collect(),
-        }
-    }
-
-    fn create_level_2() -> Self {
-        let data = vec![
-            vec![2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
-            vec![2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
-            vec![2, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 2],
-            vec![2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2],
-            vec![2, 0, 1, 0, 0, 0, 3, 3, 3, 3, 0, 0, 0, 1, 0, 2],
-            vec![2, 0, 0, 0, 0, 0, 3, 6, 6, 3, 0, 0, 0, 0, 0, 2],
-            vec![2, 0, 0, 0, 0, 0, 3, 6, 6, 3, 0, 0, 0, 0, 0, 2],
-            vec![2, 0, 1, 0, 0, 0, 3, 3, 3, 3, 0, 0, 0, 1, 0, 2],
-            vec![2, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2],
-            vec![2, 0, 1, 1, 1, 0, 0, 0, 0, 0, 0, 1, 1, 1, 0, 2],
-            vec![2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
-            vec![2, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
-            vec![2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
-        ];
-        
-        Self {
-            width: 16,
-            height: 13,
-            data: data.into_iter().map(|row| {
-                row.into_iter().map(|cell| match cell {
-                    0 => CellType::Empty,
-                    1 => CellType::Wall1,
-                    2 => CellType::Wall2,
-                    3 => CellType::Wall3,
-                    4 => CellType::Wall4,
-                    5 => CellType::Exit,
-                    6 => CellType::Hazard,
-                    _ => CellType::Empty,
-                }).collect()
-            }).collect(),
-        }
-    }
-
-    fn create_level_3() -> Self {
-        let data = vec![
-            vec![4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4],
-            vec![4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4],
-            vec![4, 0, 3, 0, 3, 0, 3, 0, 3, 0, 3, 0, 3, 0, 3, 4],
-            vec![4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4],
-            vec![4, 0, 2, 0, 2, 0, 2, 6, 2, 0, 2, 0, 2, 0, 2, 4],
-            vec![4, 0, 0, 0, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 4],
-            vec![4, 0, 1, 0, 1, 0, 1, 6, 1, 0, 1, 0, 1, 0, 1, 4],
-            vec![4, 0, 0, 0, 0, 0, 0, 6, 0, 0, 0, 0, 0, 0, 0, 4],
-            vec![4, 0, 2, 0, 2, 0, 2, 6, 2, 0, 2, 0, 2, 0, 2, 4],
-            vec![4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 4],
-            vec![4, 0, 3, 0, 3, 0, 3, 0, 3, 0, 3, 0, 3, 0, 3, 4],
-            vec![4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 5, 4],
-            vec![4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4, 4],
-        ];
-        
+    // Empty map with default surfaces, filled in by the level loader
+    pub fn new(width: usize, height: usize) -> Self {
         Self {
-            width: 16,
-            height: 13,
-            data: data.into_iter().map(|row| {
-                row.into_iter().map(|cell| match cell {
-                    0 => CellType::Empty,
-                    1 => CellType::Wall1,
-                    2 => CellType::Wall2,
-                    3 => CellType::Wall3,
-                    4 => CellType::Wall4,
-                    5 => CellType::Exit,
-                    6 => CellType::Hazard,
-                    _ => CellType::Empty,
-                }).collect()
-            }).collect(),
+            name: String::new(),
+            width,
+            height,
+            data: vec![vec![CellType::Empty; width]; height],
+            floor_data: vec![vec![FLOOR_TILES; width]; height],
+            ceiling_data: vec![vec![CEILING_PANELS; width]; height],
+            sprites: Vec::new(),
+            doors: HashMap::new(),
+            items: Vec::new(),
+            enemies: Vec::new(),
+            spawn_x: 1.5,
+            spawn_y: 1.5,
+            spawn_angle: 0.0,
+            player_radius: PLAYER_RADIUS,
+            damage_rules: DamageRules::default(),
+            music: String::from("calm"),
+            palette: [WALL_BRICK, WALL_STONE, WALL_WOOD, WALL_METAL],
         }
     }
 
@@ -137,6 +106,20 @@ impl Map {
         self.data[y][x]
     }
 
+    pub fn get_floor_texture(&self, x: usize, y: usize) -> usize {
+        if x >= self.width || y >= self.height {
+            return FLOOR_CONCRETE;
+        }
+        self.floor_data[y][x]
+    }
+
+    pub fn get_ceiling_texture(&self, x: usize, y: usize) -> usize {
+        if x >= self.width || y >= self.height {
+            return CEILING_ROCK;
+        }
+        self.ceiling_data[y][x]
+    }
+
     pub fn is_wall(&self, x: usize, y: usize) -> bool {
         match self.get_cell(x, y) {
             CellType::Wall1 | CellType::Wall2 | CellType::Wall3 | CellType::Wall4 => true,
@@ -144,6 +127,17 @@ impl Map {
         }
     }
 
+    // Walls plus doors that are not open far enough to walk through
+    pub fn is_solid(&self, x: usize, y: usize) -> bool {
+        if self.is_wall(x, y) {
+            return true;
+        }
+        match self.get_door(x, y) {
+            Some(door) => !door.is_passable(),
+            None => false,
+        }
+    }
+
     pub fn is_exit(&self, x: usize, y: usize) -> bool {
         self.get_cell(x, y) == CellType::Exit
     }
@@ -152,15 +146,124 @@ impl Map {
         self.get_cell(x, y) == CellType::Hazard
     }
 
+    // Creates door state for every door tile, oriented to slide between its neighbouring walls
+    pub fn init_doors(&mut self) {
+        self.doors.clear();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.data[y][x] == CellType::Door {
+                    let wall_west = x == 0 || self.is_wall(x - 1, y);
+                    let wall_east = self.is_wall(x + 1, y);
+                    self.doors.insert((x, y), Door::new(wall_west && wall_east));
+                }
+            }
+        }
+    }
+
+    pub fn get_door(&self, x: usize, y: usize) -> Option<&Door> {
+        self.doors.get(&(x, y))
+    }
+
+    pub fn get_door_mut(&mut self, x: usize, y: usize) -> Option<&mut Door> {
+        self.doors.get_mut(&(x, y))
+    }
+
+    pub fn open_door(&mut self, x: usize, y: usize) -> bool {
+        match self.doors.get_mut(&(x, y)) {
+            Some(door) => door.open(),
+            None => false,
+        }
+    }
+
+    // Doors stay open while any of the occupied cells (the player's, the enemies') is in them
+    pub fn update_doors(&mut self, delta_time: f64, occupied: &[(usize, usize)]) {
+        for (cell, door) in self.doors.iter_mut() {
+            door.update(delta_time, occupied.contains(cell));
+        }
+    }
+
+    // First door along the view direction within reach, stopping at walls
+    pub fn find_door_in_front(&self, x: f64, y: f64, angle: f64, reach: f64) -> Option<(usize, usize)> {
+        let steps = (reach / 0.1) as usize;
+        for i in 0..=steps {
+            let distance = i as f64 * 0.1;
+            let check_x = x + angle.cos() * distance;
+            let check_y = y + angle.sin() * distance;
+            if check_x < 0.0 || check_y < 0.0 {
+                return None;
+            }
+            let (cell_x, cell_y) = (check_x as usize, check_y as usize);
+            if self.get_door(cell_x, cell_y).is_some() {
+                return Some((cell_x, cell_y));
+            }
+            if self.is_wall(cell_x, cell_y) {
+                return None;
+            }
+        }
+        None
+    }
+
+    // The player must start on open floor that neither hurts nor ends the level
+    pub fn validate_spawn(&self) -> Result<(), SpawnError> {
+        if self.spawn_x < 0.0 || self.spawn_y < 0.0 || self.spawn_x >= self.width as f64 || self.spawn_y >= self.height as f64 {
+            return Err(SpawnError::OutOfBounds);
+        }
+        
+        let (x, y) = (self.spawn_x as usize, self.spawn_y as usize);
+        if self.is_solid(x, y) {
+            Err(SpawnError::InWall)
+        } else if self.is_hazard(x, y) {
+            Err(SpawnError::OnHazard)
+        } else if self.is_exit(x, y) {
+            Err(SpawnError::OnExit)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn get_wall_texture(&self, cell_type: CellType) -> usize {
+        match cell_type {
+            CellType::Wall1 => self.palette[0],
+            CellType::Wall2 => self.palette[1],
+            CellType::Wall3 => self.palette[2],
+            CellType::Wall4 => self.palette[3],
+            CellType::Exit => WALL_EXIT,
+            CellType::Hazard => WALL_HAZARD,
+            CellType::Door => WALL_DOOR,
+            _ => WALL_STONE,
+        }
+    }
+
+    pub fn get_door_texture(&self, door: &Door) -> usize {
+        match door.lock {
+            Some(KeyColor::Red) => WALL_DOOR_RED,
+            Some(KeyColor::Blue) => WALL_DOOR_BLUE,
+            Some(KeyColor::Yellow) => WALL_DOOR_YELLOW,
+            None => WALL_DOOR,
+        }
+    }
+
+    // Minimap color for a cell, locked doors take the color of their key
+    pub fn get_cell_color(&self, x: usize, y: usize) -> u32 {
+        match self.get_door(x, y).and_then(|door| door.lock) {
+            Some(color) => color.color(),
+            None => self.get_wall_color(self.get_cell(x, y)),
+        }
+    }
+
     pub fn get_wall_color(&self, cell_type: CellType) -> u32 {
         match cell_type {
-            CellType::Wall1 => 0xFF4444, // Red brick
-            CellType::Wall2 => 0x4444FF, // Blue stone
-            CellType::Wall3 => 0x44FF44, // Green wood
-            CellType::Wall4 => 0xFFFF44, // Yellow metal
-            CellType::Exit => 0xFF00FF,   // Magenta exit
-            CellType::Hazard => 0xFF8800, // Orange hazard
-            _ => 0x888888,                // Gray default
+            CellType::Empty => 0x000000,
+            _ => match self.get_wall_texture(cell_type) {
+                WALL_BRICK => 0xFF4444,  // Red brick
+                WALL_STONE => 0x4444FF,  // Blue stone
+                WALL_WOOD => 0x44FF44,   // Green wood
+                WALL_METAL => 0xFFFF44,  // Yellow metal
+                WALL_EXIT => 0xFF00FF,   // Magenta exit
+                WALL_HAZARD => 0xFF8800, // Orange hazard
+                WALL_DOOR => 0x8B5A2B,   // Brown door
+                _ => 0x888888,           // Gray default
+            },
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,362 @@
+// A* search over the map grid, shared by enemy AI and the level validator
+use crate::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+const DIAGONAL_COST: f64 = std::f64::consts::SQRT_2;
+const KEY_COLORS: [KeyColor; 3] = [KeyColor::Red, KeyColor::Blue, KeyColor::Yellow];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Connectivity {
+    Four,  // Orthogonal steps only
+    Eight, // Diagonal steps too, but never cutting a corner
+}
+
+#[derive(Clone, Copy)]
+pub struct PathOptions {
+    pub connectivity: Connectivity,
+    pub hazard_cost: Option<f64>, // Extra cost for entering a hazard cell, None avoids them
+    pub plan_doors: bool,         // Treat closed doors as open, locked ones once their key is held
+}
+
+impl PathOptions {
+    // Moving through the map as it is right now, the way enemies do
+    pub fn walking(connectivity: Connectivity) -> Self {
+        Self {
+            connectivity,
+            hazard_cost: None,
+            plan_doors: false,
+        }
+    }
+}
+
+pub struct Path {
+    pub cells: Vec<(usize, usize)>, // Leaves out the start, ends at the goal
+    pub cost: f64,
+    pub keys: Vec<KeyColor>, // Keys picked up along the way, in order
+}
+
+impl Path {
+    pub fn hazard_cells(&self, map: &Map) -> usize {
+        self.cells.iter().filter(|&&(x, y)| map.is_hazard(x, y)).count()
+    }
+}
+
+// Cheapest route from `from` to `to`. The goal may be a hazard even when hazards are avoided,
+// so enemies can still close in on a player standing on one.
+pub fn find_path(map: &Map, from: (usize, usize), to: (usize, usize), options: &PathOptions) -> Option<Path> {
+    search(map, from, to, options, &[])
+}
+
+// What it takes to finish a level, found by the validator
+pub struct LevelReport {
+    pub steps: usize,
+    pub hazard_cells: usize,
+    pub min_hazard_damage: i32, // Hazard damage on the least harmful route, walking at full speed
+    pub keys: Vec<KeyColor>,    // Keys that route has to pick up
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValidationError {
+    ExitUnreachable,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ValidationError::ExitUnreachable => write!(f, "no exit can be reached from the spawn"),
+        }
+    }
+}
+
+// Proves an exit can be reached from the spawn, collecting keys for locked doors on the way,
+// and finds the route that takes the least hazard damage
+pub fn validate_level(map: &Map) -> Result<LevelReport, ValidationError> {
+    // A hazard cell costs more than any detour around it, so fewer hazards always wins
+    let options = PathOptions {
+        connectivity: Connectivity::Four,
+        hazard_cost: Some((map.width * map.height) as f64),
+        plan_doors: true,
+    };
+    let keys: Vec<((usize, usize), KeyColor)> = map.items.iter().filter_map(|item| match item.kind {
+        ItemKind::Key(color) => Some(((item.x() as usize, item.y() as usize), color)),
+        _ => None,
+    }).collect();
+
+    let start = (map.spawn_x as usize, map.spawn_y as usize);
+    let best = exits(map)
+        .filter_map(|exit| search(map, start, exit, &options, &keys))
+        .min_by(|a, b| a.cost.total_cmp(&b.cost))
+        .ok_or(ValidationError::ExitUnreachable)?;
+
+    Ok(LevelReport {
+        steps: best.cells.len(),
+        hazard_cells: best.hazard_cells(map),
+        min_hazard_damage: route_hazard_damage(map, &best.cells),
+        keys: best.keys,
+    })
+}
+
+fn exits(map: &Map) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (0..map.height).flat_map(move |y| (0..map.width).map(move |x| (x, y))).filter(move |&(x, y)| map.is_exit(x, y))
+}
+
+// Each run of hazard cells hurts once on entry and, for damage over time, again every cooldown
+// spent inside it
+fn route_hazard_damage(map: &Map, cells: &[(usize, usize)]) -> i32 {
+    let rule = match map.damage_rules.hazard {
+        Some(rule) => rule,
+        None => return 0,
+    };
+    let mut damage = 0;
+    let mut run = 0;
+    for &(x, y) in cells.iter().chain(std::iter::once(&(usize::MAX, usize::MAX))) {
+        if map.is_hazard(x, y) {
+            run += 1;
+        } else if run > 0 {
+            let ticks = match rule.mode {
+                DamageMode::OverTime => {
+                    // Damage lands at most once a tick however short the cooldown
+                    let time_inside = run as f64 / PLAYER_SPEED;
+                    (time_inside / rule.cooldown.max(TICK)) as i32 + 1
+                },
+                DamageMode::OnEnter => 1,
+            };
+            damage += ticks * rule.amount;
+            run = 0;
+        }
+    }
+    damage
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct Node {
+    x: usize,
+    y: usize,
+    keys: u8, // Bit per KEY_COLORS entry
+}
+
+struct Open {
+    estimate: f64,
+    node: Node,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    // Reversed so the binary heap pops the lowest estimate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+fn key_bit(color: KeyColor) -> u8 {
+    1 << KEY_COLORS.iter().position(|&c| c == color).unwrap_or(0)
+}
+
+fn search(map: &Map, from: (usize, usize), to: (usize, usize), options: &PathOptions, keys: &[((usize, usize), KeyColor)]) -> Option<Path> {
+    if to.0 >= map.width || to.1 >= map.height || map.is_wall(to.0, to.1) {
+        return None;
+    }
+
+    let pick_up = |x: usize, y: usize, held: u8| {
+        keys.iter().filter(|(cell, _)| *cell == (x, y)).fold(held, |held, &(_, color)| held | key_bit(color))
+    };
+    let heuristic = |x: usize, y: usize| {
+        let dx = (x as f64 - to.0 as f64).abs();
+        let dy = (y as f64 - to.1 as f64).abs();
+        match options.connectivity {
+            Connectivity::Four => dx + dy,
+            Connectivity::Eight => dx.max(dy) + (DIAGONAL_COST - 1.0) * dx.min(dy),
+        }
+    };
+
+    let start = Node { x: from.0, y: from.1, keys: pick_up(from.0, from.1, 0) };
+    let mut costs: HashMap<Node, f64> = HashMap::new();
+    let mut came_from: HashMap<Node, Node> = HashMap::new();
+    let mut open = BinaryHeap::new();
+    costs.insert(start, 0.0);
+    open.push(Open { estimate: heuristic(from.0, from.1), node: start });
+
+    while let Some(Open { node, .. }) = open.pop() {
+        if (node.x, node.y) == to {
+            return Some(rebuild(node, start, &came_from, costs[&node]));
+        }
+        let cost = costs[&node];
+
+        for &(dx, dy) in neighbour_offsets(options.connectivity) {
+            let (next_x, next_y) = (node.x as i32 + dx, node.y as i32 + dy);
+            if next_x < 0 || next_y < 0 {
+                continue;
+            }
+            let (next_x, next_y) = (next_x as usize, next_y as usize);
+            let hazard_goal = (next_x, next_y) == to && map.is_hazard(next_x, next_y);
+            if !hazard_goal && !passable(map, next_x, next_y, node.keys, options) {
+                continue;
+            }
+            // Diagonal steps need both orthogonal cells free so they never clip a wall corner
+            if dx != 0 && dy != 0 && (!passable(map, next_x, node.y, node.keys, options) || !passable(map, node.x, next_y, node.keys, options)) {
+                continue;
+            }
+
+            let mut step = if dx != 0 && dy != 0 { DIAGONAL_COST } else { 1.0 };
+            if map.is_hazard(next_x, next_y) {
+                step += options.hazard_cost.unwrap_or(0.0);
+            }
+            let next = Node { x: next_x, y: next_y, keys: pick_up(next_x, next_y, node.keys) };
+            let next_cost = cost + step;
+            if costs.get(&next).is_none_or(|&known| next_cost < known) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, node);
+                open.push(Open { estimate: next_cost + heuristic(next_x, next_y), node: next });
+            }
+        }
+    }
+    None
+}
+
+fn neighbour_offsets(connectivity: Connectivity) -> &'static [(i32, i32)] {
+    match connectivity {
+        Connectivity::Four => &[(1, 0), (0, 1), (-1, 0), (0, -1)],
+        Connectivity::Eight => &[(1, 0), (0, 1), (-1, 0), (0, -1), (1, 1), (-1, 1), (1, -1), (-1, -1)],
+    }
+}
+
+fn passable(map: &Map, x: usize, y: usize, keys: u8, options: &PathOptions) -> bool {
+    if x >= map.width || y >= map.height || map.is_wall(x, y) {
+        return false;
+    }
+    if map.is_hazard(x, y) && options.hazard_cost.is_none() {
+        return false;
+    }
+    door_open(map, x, y, keys, options)
+}
+
+fn door_open(map: &Map, x: usize, y: usize, keys: u8, options: &PathOptions) -> bool {
+    match map.get_door(x, y) {
+        None => true,
+        Some(door) if options.plan_doors => door.lock.is_none_or(|color| keys & key_bit(color) != 0),
+        Some(door) => door.is_passable(),
+    }
+}
+
+fn rebuild(goal: Node, start: Node, came_from: &HashMap<Node, Node>, cost: f64) -> Path {
+    let mut cells = Vec::new();
+    let mut keys = Vec::new();
+    let mut node = goal;
+    while node != start {
+        let previous = came_from[&node];
+        for &color in KEY_COLORS.iter() {
+            if node.keys & key_bit(color) != 0 && previous.keys & key_bit(color) == 0 {
+                keys.push(color);
+            }
+        }
+        cells.push((node.x, node.y));
+        node = previous;
+    }
+    cells.reverse();
+    keys.reverse();
+    keys.splice(0..0, KEY_COLORS.iter().copied().filter(|&color| start.keys & key_bit(color) != 0));
+
+    Path { cells, cost, keys }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Map from rows of level tiles, P marks the spawn
+    fn grid(rows: &[&str]) -> Map {
+        let mut map = Map::new(rows[0].len(), rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, tile) in row.chars().enumerate() {
+                if tile == 'P' {
+                    map.spawn_x = x as f64 + 0.5;
+                    map.spawn_y = y as f64 + 0.5;
+                } else {
+                    map.data[y][x] = CellType::from_tile(tile).unwrap();
+                }
+            }
+        }
+        map
+    }
+
+    #[test]
+    fn eight_connectivity_cuts_across_but_not_around_corners() {
+        let room = grid(&["11111", "1P001", "10001", "10001", "11111"]);
+        let four = find_path(&room, (1, 1), (3, 3), &PathOptions::walking(Connectivity::Four)).unwrap();
+        let eight = find_path(&room, (1, 1), (3, 3), &PathOptions::walking(Connectivity::Eight)).unwrap();
+        assert_eq!((four.cells.len(), four.cost), (4, 4.0));
+        assert_eq!(eight.cells, vec![(2, 2), (3, 3)]);
+        assert_eq!(eight.cost, 2.0 * DIAGONAL_COST);
+
+        let corner = grid(&["1111", "1P01", "1101", "1111"]);
+        let path = find_path(&corner, (1, 1), (2, 2), &PathOptions::walking(Connectivity::Eight)).unwrap();
+        assert_eq!(path.cells, vec![(2, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn hazard_cost_decides_between_shortcut_and_detour() {
+        let map = grid(&["11111", "1P601", "10001", "11111"]);
+        let with_cost = |hazard_cost| PathOptions { hazard_cost, ..PathOptions::walking(Connectivity::Four) };
+
+        let avoided = find_path(&map, (1, 1), (3, 1), &with_cost(None)).unwrap();
+        assert_eq!(avoided.hazard_cells(&map), 0);
+        assert_eq!(avoided.cells.len(), 4);
+
+        let cheap = find_path(&map, (1, 1), (3, 1), &with_cost(Some(1.0))).unwrap();
+        assert_eq!(cheap.cells, vec![(2, 1), (3, 1)]);
+        assert_eq!(cheap.cost, 3.0);
+
+        let expensive = find_path(&map, (1, 1), (3, 1), &with_cost(Some(5.0))).unwrap();
+        assert_eq!(expensive.hazard_cells(&map), 0);
+
+        // A hazard goal is still reached when hazards are avoided
+        let onto = find_path(&map, (1, 1), (2, 1), &with_cost(None)).unwrap();
+        assert_eq!(onto.cells, vec![(2, 1)]);
+    }
+
+    #[test]
+    fn walled_off_exit_is_unreachable() {
+        let map = grid(&["11111", "1P151", "11111"]);
+        assert_eq!(validate_level(&map).err(), Some(ValidationError::ExitUnreachable));
+        assert!(find_path(&map, (1, 1), (3, 1), &PathOptions::walking(Connectivity::Eight)).is_none());
+    }
+
+    #[test]
+    fn min_hazard_damage_counts_each_run_of_hazards() {
+        // The only corridor crosses a run of one hazard cell and a run of two
+        let mut map = grid(&["11111111", "1P606651", "11111111"]);
+        map.damage_rules.hazard = Some(DamageRule { amount: 7, cooldown: 0.5, mode: DamageMode::OnEnter });
+        let report = validate_level(&map).unwrap();
+        assert_eq!((report.steps, report.hazard_cells), (5, 3));
+        assert_eq!(report.min_hazard_damage, 14);
+
+        // Two thirds of a second in the long run is one extra cooldown
+        map.damage_rules.hazard = Some(DamageRule { amount: 5, cooldown: 0.5, mode: DamageMode::OverTime });
+        assert_eq!(validate_level(&map).unwrap().min_hazard_damage, 15);
+
+        map.damage_rules.hazard = None;
+        assert_eq!(validate_level(&map).unwrap().min_hazard_damage, 0);
+    }
+
+    #[test]
+    fn validator_prefers_the_route_without_hazards() {
+        let map = grid(&["1111111", "1P66651", "1000001", "1111111"]);
+        let report = validate_level(&map).unwrap();
+        assert_eq!((report.steps, report.hazard_cells, report.min_hazard_damage), (6, 0, 0));
+        assert!(report.keys.is_empty());
+    }
+}
@@ -1,11 +1,39 @@
+use crate::collision::move_circle;
+use crate::items::*;
 use crate::map::Map;
 
+pub const PLAYER_SPEED: f64 = 3.0; // Cells per second
+pub const PLAYER_RADIUS: f64 = 0.2; // Cells, unless the level sets its own
+
+// Everything picked up during the current level
+pub struct Inventory {
+    pub keys: Vec<KeyColor>,
+}
+
+impl Inventory {
+    pub fn new() -> Self {
+        Self { keys: Vec::new() }
+    }
+
+    pub fn add_key(&mut self, color: KeyColor) {
+        if !self.has_key(color) {
+            self.keys.push(color);
+        }
+    }
+
+    pub fn has_key(&self, color: KeyColor) -> bool {
+        self.keys.contains(&color)
+    }
+}
+
 pub struct Player {
     pub x: f64,
     pub y: f64,
     pub angle: f64,
     pub speed: f64,
+    pub radius: f64, // Collision circle, in cells
     pub rotation_speed: f64,
+    pub inventory: Inventory,
 }
 
 impl Player {
@@ -14,37 +42,19 @@ impl Player {
             x,
             y,
             angle,
-            speed: 3.0,
+            speed: PLAYER_SPEED,
+            radius: PLAYER_RADIUS,
             rotation_speed: 2.0,
+            inventory: Inventory::new(),
         }
     }
 
+    // Returns true when a wall took away part of the movement
     pub fn update(&mut self, delta_time: f64, move_x: f64, move_y: f64, map: &Map) -> bool {
         let move_speed = self.speed * delta_time;
-        
-        // Calculate new position
-        let new_x = self.x + move_x * move_speed;
-        let new_y = self.y + move_y * move_speed;
-        
-        // Collision detection with walls
-        let collision_margin = 0.1;
-        let mut hit_wall = false;
-        
-        // Check X movement
-        if !map.is_wall((new_x + collision_margin * move_x.signum()) as usize, self.y as usize) &&
-           !map.is_wall((new_x - collision_margin * move_x.signum()) as usize, self.y as usize) {
-            self.x = new_x;
-        } else if move_x.abs() > 0.01 {
-            hit_wall = true;
-        }
-        
-        // Check Y movement
-        if !map.is_wall(self.x as usize, (new_y + collision_margin * move_y.signum()) as usize) &&
-           !map.is_wall(self.x as usize, (new_y - collision_margin * move_y.signum()) as usize) {
-            self.y = new_y;
-        } else if move_y.abs() > 0.01 {
-            hit_wall = true;
-        }
+        let (x, y, hit_wall) = move_circle(map, self.x, self.y, self.radius, move_x * move_speed, move_y * move_speed);
+        self.x = x;
+        self.y = y;
         
         // Keep angle in valid range
         while self.angle < 0.0 {
@@ -1,9 +1,38 @@
 use crate::*;
 use std::f64::consts::PI;
 
+const CEILING_COLOR: u32 = 0x87CEEB; // Sky blue, outside the map
+const FLOOR_COLOR: u32 = 0x404040; // Dark gray, outside the map
+const FOG_COLOR: u32 = 0x708090; // Slate gray fog
+
+pub struct RayHit {
+    pub distance: f64,
+    pub texture: usize, // Wall texture id
+    pub side: bool, // false if x-side, true if y-side
+    pub wall_x: f64, // Exact hit position along the wall face (0.0..1.0)
+}
+
+// The frame being drawn
+struct RenderTarget<'a> {
+    buffer: &'a mut [u32],
+    width: usize,
+    height: usize,
+}
+
+// What a screen column looks at and how it is lit
+struct ColumnView<'a> {
+    x: usize,
+    ray_angle: f64,
+    player: &'a Player,
+    map: &'a Map,
+    effects: &'a Effects,
+}
+
 pub struct RayCaster {
     pub fov: f64,
     pub max_depth: f64,
+    pub textures: TextureManager,
+    pub z_buffer: Vec<f64>, // Wall distance per screen column, used to hide sprites behind walls
 }
 
 impl RayCaster {
@@ -11,26 +40,110 @@ impl RayCaster {
         Self {
             fov: PI / 3.0, // 60 degrees
             max_depth: 20.0,
+            textures: TextureManager::new(),
+            z_buffer: Vec::new(),
         }
     }
 
-    pub fn render(&self, buffer: &mut Vec<u32>, player: &Player, map: &Map, effects: &Effects, window_width: usize, window_height: usize) {
+    pub fn render(&mut self, buffer: &mut Vec<u32>, player: &Player, map: &Map, effects: &Effects, window_width: usize, window_height: usize) {
         let half_fov = self.fov / 2.0;
         let angle_step = self.fov / window_width as f64;
+        self.z_buffer.resize(window_width, self.max_depth);
+        let mut target = RenderTarget { buffer, width: window_width, height: window_height };
         
         for x in 0..window_width {
             let ray_angle = player.angle - half_fov + (x as f64 * angle_step);
-            let (distance, wall_type, hit_side) = self.cast_ray(player.x, player.y, ray_angle, map);
+            let hit = self.cast_ray(player.x, player.y, ray_angle, map);
+            self.z_buffer[x] = hit.distance;
             
-            if distance < self.max_depth {
-                self.draw_wall_slice(buffer, x, distance, wall_type, hit_side, map, effects, ray_angle, player, window_width, window_height);
+            let view = ColumnView { x, ray_angle, player, map, effects };
+            if hit.distance < self.max_depth {
+                self.draw_wall_slice(&mut target, &view, &hit);
             } else {
-                self.draw_background_slice(buffer, x, window_width, window_height);
+                self.draw_background_slice(&mut target, &view);
+            }
+        }
+    }
+
+    // Draws billboarded sprites after the walls, far to near, clipped against the z-buffer
+    pub fn render_sprites(&self, buffer: &mut Vec<u32>, player: &Player, sprites: &[&Sprite], effects: &Effects, window_width: usize, window_height: usize) {
+        let half_fov = self.fov / 2.0;
+        let angle_step = self.fov / window_width as f64;
+        
+        let mut visible: Vec<(f64, f64, &Sprite)> = sprites.iter().filter_map(|&sprite| {
+            let dx = sprite.x - player.x;
+            let dy = sprite.y - player.y;
+            let distance = (dx * dx + dy * dy).sqrt();
+            if distance < 0.2 || distance >= self.max_depth {
+                return None;
+            }
+            
+            // Angle between view direction and sprite, wrapped to -PI..PI
+            let mut angle = dy.atan2(dx) - player.angle;
+            while angle > PI { angle -= 2.0 * PI; }
+            while angle < -PI { angle += 2.0 * PI; }
+            
+            // Keep sprites whose body may still reach into the view
+            let angular_size = (0.5 / distance).atan();
+            if angle.abs() > half_fov + angular_size {
+                return None;
+            }
+            Some((distance, angle, sprite))
+        }).collect();
+        
+        // Painter's algorithm: farthest first so nearer sprites overwrite them
+        visible.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        
+        for (distance, angle, sprite) in visible {
+            let texture = self.textures.sprite_texture(sprite.current_texture());
+            
+            // Same projection as the walls: one unit is window_height / distance pixels tall
+            let size = window_height as f64 / distance;
+            let sprite_height = size * sprite.scale;
+            let screen_x = (angle + half_fov) / angle_step;
+            let floor_y = window_height as f64 / 2.0 + size / 2.0;
+            let top = floor_y - sprite_height;
+            let left = screen_x - sprite_height / 2.0;
+            
+            let start_x = left.max(0.0) as usize;
+            let end_x = ((left + sprite_height).max(0.0) as usize).min(window_width);
+            let start_y = top.max(0.0) as usize;
+            let end_y = (floor_y.max(0.0) as usize).min(window_height);
+            
+            let lighting = effects.get_flashlight_intensity(angle) * self.distance_shade(distance);
+            
+            for x in start_x..end_x {
+                if distance >= self.z_buffer[x] {
+                    continue;
+                }
+                let texture_x = ((x as f64 - left) / sprite_height * texture.width as f64) as usize;
+                
+                for y in start_y..end_y {
+                    let texture_y = ((y as f64 - top) / sprite_height * texture.height as f64) as usize;
+                    let texel = texture.sample(texture_x.min(texture.width - 1), texture_y.min(texture.height - 1));
+                    if Texture::is_transparent(texel) {
+                        continue;
+                    }
+                    
+                    let color = self.apply_lighting(texel, lighting);
+                    buffer[y * window_width + x] = self.apply_fog(color, distance, effects);
+                }
             }
         }
     }
 
-    fn cast_ray(&self, start_x: f64, start_y: f64, angle: f64, map: &Map) -> (f64, CellType, bool) {
+    // True when no wall or closed door stands between the two points
+    pub fn has_line_of_sight(&self, from_x: f64, from_y: f64, to_x: f64, to_y: f64, map: &Map) -> bool {
+        let dx = to_x - from_x;
+        let dy = to_y - from_y;
+        let distance = (dx * dx + dy * dy).sqrt();
+        if distance < 0.001 {
+            return true;
+        }
+        self.cast_ray(from_x, from_y, dy.atan2(dx), map).distance >= distance
+    }
+
+    pub fn cast_ray(&self, start_x: f64, start_y: f64, angle: f64, map: &Map) -> RayHit {
         let dx = angle.cos();
         let dy = angle.sin();
         
@@ -75,7 +188,14 @@ impl RayCaster {
             
             // Check if ray has hit a wall
             if map_x < 0 || map_y < 0 || map_x >= map.width as i32 || map_y >= map.height as i32 {
-                return (self.max_depth, CellType::Empty, false);
+                return self.no_hit();
+            }
+            
+            if let Some(door) = map.get_door(map_x as usize, map_y as usize) {
+                if let Some(door_hit) = self.hit_door((start_x, start_y), (dx, dy), (map_x, map_y), door, map) {
+                    return door_hit;
+                }
+                continue;
             }
             
             if map.is_wall(map_x as usize, map_y as usize) {
@@ -85,7 +205,7 @@ impl RayCaster {
         
         // If we hit max iterations without finding a wall, return max depth
         if iterations >= MAX_ITERATIONS {
-            return (self.max_depth, CellType::Empty, false);
+            return self.no_hit();
         }
         
         // Calculate distance
@@ -95,97 +215,185 @@ impl RayCaster {
             (map_y as f64 - start_y + (1.0 - step_y as f64) / 2.0) / dy
         };
         
+        // Exact point along the wall face where the ray hit
+        let mut wall_x = if !side {
+            start_y + perp_wall_dist * dy
+        } else {
+            start_x + perp_wall_dist * dx
+        };
+        wall_x -= wall_x.floor();
+        
         let cell_type = map.get_cell(map_x as usize, map_y as usize);
-        (perp_wall_dist, cell_type, side)
+        RayHit {
+            distance: perp_wall_dist,
+            texture: map.get_wall_texture(cell_type),
+            side,
+            wall_x,
+        }
+    }
+
+    // Doors sit recessed half a cell into their tile and slide sideways as they open
+    fn hit_door(&self, origin: (f64, f64), direction: (f64, f64), cell: (i32, i32), door: &Door, map: &Map) -> Option<RayHit> {
+        let ((start_x, start_y), (dx, dy), (map_x, map_y)) = (origin, direction, cell);
+        let (distance, along) = if door.horizontal {
+            if dy.abs() < 1e-9 {
+                return None;
+            }
+            let distance = (map_y as f64 + 0.5 - start_y) / dy;
+            (distance, start_x + distance * dx - map_x as f64)
+        } else {
+            if dx.abs() < 1e-9 {
+                return None;
+            }
+            let distance = (map_x as f64 + 0.5 - start_x) / dx;
+            (distance, start_y + distance * dy - map_y as f64)
+        };
+        
+        // The ray leaves the cell before reaching the panel, or passes through the opened gap
+        if distance < 0.0 || !(0.0..1.0).contains(&along) || along < door.openness {
+            return None;
+        }
+        
+        Some(RayHit {
+            distance,
+            texture: map.get_door_texture(door),
+            side: door.horizontal,
+            wall_x: along - door.openness,
+        })
+    }
+
+    fn no_hit(&self) -> RayHit {
+        RayHit {
+            distance: self.max_depth,
+            texture: WALL_STONE,
+            side: false,
+            wall_x: 0.0,
+        }
     }
 
-    fn draw_wall_slice(&self, buffer: &mut Vec<u32>, x: usize, distance: f64, wall_type: CellType, hit_side: bool, map: &Map, effects: &Effects, ray_angle: f64, player: &Player, window_width: usize, window_height: usize) {
-        // Calculate wall height based on distance
-        let wall_height = (window_height as f64 / distance).min(window_height as f64);
-        let wall_start = ((window_height as f64 - wall_height) / 2.0) as usize;
-        let wall_end = (wall_start + wall_height as usize).min(window_height);
+    fn draw_wall_slice(&self, target: &mut RenderTarget, view: &ColumnView, hit: &RayHit) {
+        let window_height = target.height;
+        // Calculate wall height based on distance (unclamped so textures keep their proportions up close)
+        let line_height = window_height as f64 / hit.distance;
+        let line_start = (window_height as f64 - line_height) / 2.0;
+        let wall_start = line_start.max(0.0) as usize;
+        let wall_end = ((line_start + line_height).max(0.0) as usize).min(window_height);
         
-        // Get base wall color
-        let base_color = map.get_wall_color(wall_type);
+        let texture = self.textures.wall_texture(hit.texture);
+        
+        // Horizontal texture coordinate, mirrored so textures read the same from both sides
+        let dx = view.ray_angle.cos();
+        let dy = view.ray_angle.sin();
+        let mut texture_x = (hit.wall_x * texture.width as f64) as usize;
+        if (!hit.side && dx > 0.0) || (hit.side && dy < 0.0) {
+            texture_x = texture.width - texture_x - 1;
+        }
         
         // Calculate lighting with flashlight effect
-        let mut lighting = if hit_side { 0.7 } else { 1.0 };
+        let mut lighting = if hit.side { 0.7 } else { 1.0 };
         
         // Apply flashlight effect
-        let angle_diff = ray_angle - player.angle;
-        let flashlight_intensity = effects.get_flashlight_intensity(angle_diff);
-        lighting *= flashlight_intensity;
+        let angle_diff = view.ray_angle - view.player.angle;
+        let flashlight_intensity = view.effects.get_flashlight_intensity(angle_diff);
+        lighting *= flashlight_intensity * self.distance_shade(hit.distance);
         
-        // Extract RGB components and apply lighting
-        let r = ((base_color >> 16) & 0xFF) as f64;
-        let g = ((base_color >> 8) & 0xFF) as f64;
-        let b = (base_color & 0xFF) as f64;
-        
-        let lit_r = (r * lighting) as u32;
-        let lit_g = (g * lighting) as u32;
-        let lit_b = (b * lighting) as u32;
+        for y in wall_start..wall_end {
+            // Wall with texture
+            let wall_y = y as f64 - line_start;
+            let texture_y = (wall_y / line_height * texture.height as f64) as usize;
+            let color = self.apply_wall_texture(texture, texture_x, texture_y, lighting);
+            target.buffer[y * target.width + view.x] = self.apply_fog(color, hit.distance, view.effects);
+        }
         
-        let final_color = (lit_r << 16) | (lit_g << 8) | lit_b;
+        self.draw_floor_and_ceiling(target, view, wall_start, wall_end);
+    }
+
+    fn draw_background_slice(&self, target: &mut RenderTarget, view: &ColumnView) {
+        let horizon = target.height / 2;
+        self.draw_floor_and_ceiling(target, view, horizon, horizon);
+    }
+
+    // Per-pixel floor and ceiling casting for the parts of a column not covered by a wall
+    fn draw_floor_and_ceiling(&self, target: &mut RenderTarget, view: &ColumnView, ceiling_end: usize, floor_start: usize) {
+        let (player, map, effects) = (view.player, view.map, view.effects);
+        let buffer = &mut *target.buffer;
+        let dx = view.ray_angle.cos();
+        let dy = view.ray_angle.sin();
+        let half_height = target.height as f64 / 2.0;
+        let flashlight_intensity = effects.get_flashlight_intensity(view.ray_angle - player.angle);
         
-        let ceiling_color = 0x87CEEB; // Sky blue
-        let floor_color = 0x404040; // Dark gray
+        // Ceiling rows mirror floor rows around the horizon, so one distance formula serves both
+        let rows = (0..ceiling_end).map(|y| (y, half_height - (y as f64 + 0.5), true))
+            .chain((floor_start..target.height).map(|y| (y, y as f64 + 0.5 - half_height, false)));
         
-        // Optimized single loop for entire column
-        for y in 0..window_height {
-            let pixel_index = y * window_width + x;
+        for (y, row_offset, is_ceiling) in rows {
+            let pixel_index = y * target.width + view.x;
+            if row_offset <= 0.0 {
+                buffer[pixel_index] = if is_ceiling { CEILING_COLOR } else { FLOOR_COLOR };
+                continue;
+            }
             
-            if y < wall_start {
-                // Ceiling
-                buffer[pixel_index] = ceiling_color;
-            } else if y < wall_end {
-                // Wall with texture
-                let wall_y = y - wall_start;
-                let texture_y = (wall_y as f64 / wall_height * 64.0) as usize % 64;
-                let texture_color = self.apply_wall_texture(final_color, texture_y, wall_type);
-                buffer[pixel_index] = texture_color;
-            } else {
-                // Floor
-                buffer[pixel_index] = floor_color;
+            // Distance to the point on the floor (or ceiling) seen through this pixel
+            let distance = half_height / row_offset;
+            let world_x = player.x + dx * distance;
+            let world_y = player.y + dy * distance;
+
+            // Past the view distance only the fog is left
+            if distance >= self.max_depth {
+                buffer[pixel_index] = self.apply_fog(0x000000, distance, effects);
+                continue;
             }
-        }
-    }
 
-    fn draw_background_slice(&self, buffer: &mut Vec<u32>, x: usize, window_width: usize, window_height: usize) {
-        for y in 0..window_height {
-            if y < window_height / 2 {
-                buffer[y * window_width + x] = 0x87CEEB; // Sky blue ceiling
-            } else {
-                buffer[y * window_width + x] = 0x404040; // Dark gray floor
+            if world_x < 0.0 || world_y < 0.0 || world_x >= map.width as f64 || world_y >= map.height as f64 {
+                buffer[pixel_index] = if is_ceiling { CEILING_COLOR } else { FLOOR_COLOR };
+                continue;
             }
+            
+            let cell_x = world_x as usize;
+            let cell_y = world_y as usize;
+            let texture = if is_ceiling {
+                self.textures.surface_texture(map.get_ceiling_texture(cell_x, cell_y))
+            } else {
+                self.textures.surface_texture(map.get_floor_texture(cell_x, cell_y))
+            };
+            
+            let texture_x = ((world_x - cell_x as f64) * texture.width as f64) as usize;
+            let texture_y = ((world_y - cell_y as f64) * texture.height as f64) as usize;
+            
+            // Ceilings are a little darker than floors, like the wall side shading
+            let base_lighting = if is_ceiling { 0.8 } else { 1.0 };
+            let lighting = base_lighting * flashlight_intensity * self.distance_shade(distance);
+            let color = self.apply_wall_texture(texture, texture_x, texture_y, lighting);
+            buffer[pixel_index] = self.apply_fog(color, distance, effects);
         }
     }
 
-    fn apply_wall_texture(&self, base_color: u32, _texture_y: usize, _wall_type: CellType) -> u32 {
-        base_color
+    // Shared by walls, floors and ceilings so surfaces at the same depth get the same light
+    fn distance_shade(&self, distance: f64) -> f64 {
+        (1.0 - distance / self.max_depth * 0.75).max(0.25)
     }
 
-    fn get_ceiling_color(&self, y: usize, effects: &Effects, window_height: usize) -> u32 {
-        let base_color = 0x87CEEB; // Sky blue
-        
+    fn apply_fog(&self, color: u32, distance: f64, effects: &Effects) -> u32 {
         if effects.fog_enabled {
-            let fog_factor = (y as f64 / (window_height as f64 / 2.0)).min(1.0);
-            let fog_color = 0x708090; // Slate gray fog
-            self.blend_colors(base_color, fog_color, fog_factor * 0.3)
+            let fog_factor = (distance / self.max_depth).min(1.0);
+            self.blend_colors(color, FOG_COLOR, fog_factor * 0.5)
         } else {
-            base_color
+            color
         }
     }
 
-    fn get_floor_color(&self, y: usize, effects: &Effects, window_height: usize) -> u32 {
-        let base_color = 0x404040; // Dark gray
+    fn apply_wall_texture(&self, texture: &Texture, texture_x: usize, texture_y: usize, lighting: f64) -> u32 {
+        let texel = texture.sample(texture_x, texture_y.min(texture.height - 1));
+        self.apply_lighting(texel, lighting)
+    }
+
+    fn apply_lighting(&self, texel: u32, lighting: f64) -> u32 {
+        // Extract RGB components and apply lighting
+        let r = (((texel >> 16) & 0xFF) as f64 * lighting).min(255.0) as u32;
+        let g = (((texel >> 8) & 0xFF) as f64 * lighting).min(255.0) as u32;
+        let b = ((texel & 0xFF) as f64 * lighting).min(255.0) as u32;
         
-        if effects.fog_enabled {
-            let fog_factor = ((window_height - y) as f64 / (window_height as f64 / 2.0)).min(1.0);
-            let fog_color = 0x708090; // Slate gray fog
-            self.blend_colors(base_color, fog_color, fog_factor * 0.3)
-        } else {
-            base_color
-        }
+        (r << 16) | (g << 8) | b
     }
 
     fn blend_colors(&self, color1: u32, color2: u32, factor: f64) -> u32 {
@@ -0,0 +1,298 @@
+// Input recordings. The input of every simulation tick is kept, so playing a recording back from
+// the same seed and saved progress reproduces the run exactly. Stored as `name = value` lines:
+//
+//   version = 3
+//   tick_rate = 60
+//   seed = 8731542
+//   save.version = 2            progress the run started from, as in save.cfg
+//   scores.last_name = ALEX     high scores it started from, as in scores.cfg
+//   input = 90 0                90 ticks with nothing held (hex mask of held actions)
+//   input = 1 1 0.0125 0        one tick holding move_forward that also turned the mouse
+//   input = 1 0 0 0 Key1 C      one tick with raw keys pressed, for the controls menu
+//   ticks = 92
+//   checksum = 5c1f0e2a9b7d3f10 game state at the end, to tell when playback diverged
+//
+// Record with --record FILE, play back in the window with --replay FILE or headlessly with
+// --headless --replay FILE. The levels must be the ones the run was recorded with.
+use crate::*;
+use minifb::Key;
+use std::io;
+use std::path::Path;
+
+pub const RECORD_FLAG: &str = "--record";
+pub const REPLAY_FLAG: &str = "--replay";
+const REPLAY_VERSION: u32 = 3; // Raised whenever state_checksum covers more of the game
+
+// Longest recording accepted, four hours of play, so a corrupt repeat count cannot exhaust memory
+const MAX_TICKS: usize = 4 * 60 * 60 * TICK_RATE as usize;
+
+// Mask bit for a lost window focus, above the bits of all_actions()
+const FOCUS_LOST_BIT: u32 = 1 << 31;
+
+#[derive(Clone, PartialEq, Default)]
+pub struct TickInput {
+    pub actions: u32, // Bit i set while all_actions().nth(i) is held, plus FOCUS_LOST_BIT
+    pub mouse_delta: (f64, f64),
+    pub keys_pressed: Vec<Key>, // Only bindable keys, the rest have no effect
+}
+
+impl TickInput {
+    pub fn capture(input: &InputState) -> Self {
+        let mut actions = if input.focus_lost { FOCUS_LOST_BIT } else { 0 };
+        for (bit, action) in all_actions().enumerate() {
+            if input.is_held(action) {
+                actions |= 1 << bit;
+            }
+        }
+        Self {
+            actions,
+            mouse_delta: input.mouse_delta,
+            keys_pressed: input.keys_pressed.iter().copied().filter(|&key| key_from_name(&key_name(key)).is_some()).collect(),
+        }
+    }
+
+    pub fn apply(&self, input: &mut InputState) {
+        let held = all_actions().enumerate().filter(|&(bit, _)| self.actions & (1 << bit) != 0).map(|(_, action)| action);
+        input.update(held, self.keys_pressed.clone(), self.mouse_delta);
+        input.focus_lost = self.actions & FOCUS_LOST_BIT != 0;
+    }
+
+    // The part of an `input` line after the repeat count
+    fn to_text(&self) -> String {
+        let mut text = format!("{:x}", self.actions);
+        if self.mouse_delta != (0.0, 0.0) || !self.keys_pressed.is_empty() {
+            text.push_str(&format!(" {} {}", self.mouse_delta.0, self.mouse_delta.1));
+        }
+        for &key in &self.keys_pressed {
+            text.push_str(&format!(" {}", key_name(key)));
+        }
+        text
+    }
+
+    fn from_words(words: &[&str]) -> Result<Self, String> {
+        let actions = u32::from_str_radix(words[0], 16).map_err(|_| format!("invalid action mask `{}`", words[0]))?;
+        let mut tick = Self { actions, ..Default::default() };
+        if words.len() > 1 {
+            if words.len() < 3 {
+                return Err(String::from("expected both mouse deltas"));
+            }
+            let delta = |text: &str| text.parse::<f64>().map_err(|_| format!("invalid mouse delta `{}`", text));
+            tick.mouse_delta = (delta(words[1])?, delta(words[2])?);
+        }
+        for name in words.iter().skip(3) {
+            tick.keys_pressed.push(key_from_name(name).ok_or_else(|| format!("unknown key `{}`", name))?);
+        }
+        Ok(tick)
+    }
+}
+
+pub struct Replay {
+    pub seed: u64,
+    pub progress: SaveData, // Saved progress when the run began
+    pub scores: Leaderboard, // High scores when the run began, they decide when a name is asked for
+    pub ticks: Vec<TickInput>,
+    pub checksum: Option<u64>, // Game state after the last tick, missing if the file was cut short
+}
+
+impl Replay {
+    // Starts recording a game that has not run any tick yet
+    pub fn start(game: &Game) -> Self {
+        Self {
+            seed: game.seed,
+            progress: game.progress.clone(),
+            scores: game.leaderboard.clone(),
+            ticks: Vec::new(),
+            checksum: None,
+        }
+    }
+
+    // Keeps the input of the tick about to run
+    pub fn record(&mut self, input: &InputState) {
+        self.ticks.push(TickInput::capture(input));
+    }
+
+    // A game set up like the recorded one, before its first tick. Controls and settings are the
+    // defaults, and the player's files are neither read nor written.
+    pub fn new_game(&self, audio: AudioSystem) -> Game {
+        let mut game = Game::with_defaults(audio);
+        game.reseed(self.seed);
+        game.progress = self.progress.clone();
+        game.leaderboard = self.scores.clone();
+        game
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let entries = read_config(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::from_entries(entries).map_err(|message| format!("{}: {}", path.display(), message))
+    }
+
+    fn from_entries(entries: Vec<ConfigEntry>) -> Result<Self, String> {
+        let mut replay = Self {
+            seed: 0,
+            progress: SaveData::new(),
+            scores: Leaderboard::new(),
+            ticks: Vec::new(),
+            checksum: None,
+        };
+        let mut version = None;
+        let mut save_entries = Vec::new();
+        let mut score_entries = Vec::new();
+        let mut expected_ticks = None;
+
+        for entry in entries {
+            let at_line = |message: String| format!("line {}: {}", entry.line, message);
+            let value = entry.value.as_str();
+            if let Some(name) = entry.name.strip_prefix("save.") {
+                save_entries.push(ConfigEntry { line: entry.line, name: name.to_string(), value: entry.value.clone() });
+                continue;
+            }
+            if let Some(name) = entry.name.strip_prefix("scores.") {
+                score_entries.push(ConfigEntry { line: entry.line, name: name.to_string(), value: entry.value.clone() });
+                continue;
+            }
+            match entry.name.as_str() {
+                "version" => {
+                    let number: u32 = value.parse().map_err(|_| at_line(format!("invalid version `{}`", value)))?;
+                    if number != REPLAY_VERSION {
+                        return Err(at_line(format!("unsupported version {} (this game plays version {})", number, REPLAY_VERSION)));
+                    }
+                    version = Some(number);
+                },
+                "tick_rate" => {
+                    if value.parse::<u32>().ok() != Some(TICK_RATE) {
+                        return Err(at_line(format!("recorded at {} ticks per second, this game runs at {}", value, TICK_RATE)));
+                    }
+                },
+                "seed" => replay.seed = value.parse().map_err(|_| at_line(format!("invalid seed `{}`", value)))?,
+                "input" => {
+                    let words: Vec<&str> = value.split_whitespace().collect();
+                    if words.len() < 2 {
+                        return Err(at_line(String::from("expected `count mask`")));
+                    }
+                    let count: usize = words[0].parse().map_err(|_| at_line(format!("invalid count `{}`", words[0])))?;
+                    if count > MAX_TICKS - replay.ticks.len() {
+                        return Err(at_line(format!("recording longer than {} ticks", MAX_TICKS)));
+                    }
+                    let tick = TickInput::from_words(&words[1..]).map_err(at_line)?;
+                    replay.ticks.extend(std::iter::repeat_n(tick, count));
+                },
+                "ticks" => expected_ticks = Some(value.parse::<usize>().map_err(|_| at_line(format!("invalid tick count `{}`", value)))?),
+                "checksum" => replay.checksum = Some(u64::from_str_radix(value, 16).map_err(|_| at_line(format!("invalid checksum `{}`", value)))?),
+                _ => return Err(at_line(format!("unknown entry `{}`", entry.name))),
+            }
+        }
+
+        if version.is_none() {
+            return Err(String::from("missing version line"));
+        }
+        if let Some(expected) = expected_ticks.filter(|&expected| expected != replay.ticks.len()) {
+            return Err(format!("expected {} ticks, found {}", expected, replay.ticks.len()));
+        }
+        replay.progress = SaveData::from_entries(save_entries).map_err(|message| format!("saved progress: {}", message))?;
+        let mut score_error = None;
+        replay.scores = Leaderboard::from_entries(score_entries, |line, message| {
+            score_error.get_or_insert(format!("line {}: {}", line, message));
+        });
+        if let Some(message) = score_error {
+            return Err(message);
+        }
+        Ok(replay)
+    }
+
+    // Writes the recording with the state the game ended in
+    pub fn save_to(&mut self, path: &Path, game: &Game) -> io::Result<()> {
+        self.checksum = Some(state_checksum(game));
+        let mut entries = vec![
+            (String::from("version"), REPLAY_VERSION.to_string()),
+            (String::from("tick_rate"), TICK_RATE.to_string()),
+            (String::from("seed"), self.seed.to_string()),
+        ];
+        for (name, value) in self.progress.entries() {
+            entries.push((format!("save.{}", name), value));
+        }
+        for (name, value) in self.scores.entries() {
+            entries.push((format!("scores.{}", name), value));
+        }
+
+        // Runs of identical ticks share a line
+        let mut start = 0;
+        while start < self.ticks.len() {
+            let tick = &self.ticks[start];
+            let count = self.ticks[start..].iter().take_while(|&other| other == tick).count();
+            entries.push((String::from("input"), format!("{} {}", count, tick.to_text())));
+            start += count;
+        }
+
+        entries.push((String::from("ticks"), self.ticks.len().to_string()));
+        if let Some(checksum) = self.checksum {
+            entries.push((String::from("checksum"), format!("{:016x}", checksum)));
+        }
+        write_config(path, "Input recording, play it back with --replay FILE.", &entries)
+    }
+}
+
+// Feeds a recording into the game one tick at a time
+pub struct Playback {
+    pub replay: Replay,
+    pub tick: usize, // Next tick to play
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self { replay, tick: 0 }
+    }
+
+    // Loads the next recorded tick into input, false once the recording is over
+    pub fn next_tick(&mut self, input: &mut InputState) -> bool {
+        match self.replay.ticks.get(self.tick) {
+            Some(tick) => {
+                tick.apply(input);
+                self.tick += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Whether the game ended up where the recorded one did
+    pub fn verify(&self, game: &Game) -> Result<(), String> {
+        match self.replay.checksum {
+            Some(checksum) if checksum == state_checksum(game) => Ok(()),
+            Some(_) => Err(String::from("the game state differs from the recording, the game or its levels changed since")),
+            None => Err(String::from("the recording has no checksum to compare with")),
+        }
+    }
+}
+
+// FNV-1a over everything the simulation decides, stable between builds and platforms. New
+// simulation state goes in here too, along with a new REPLAY_VERSION.
+pub fn state_checksum(game: &Game) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut add = |value: u64| {
+        for byte in value.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    add(game.state as u64);
+    add(game.current_level as u64);
+    add(game.endless.map_or(0, |run| run.seed ^ ((run.depth as u64) << 32)));
+    add(game.player.x.to_bits());
+    add(game.player.y.to_bits());
+    add(game.player.angle.to_bits());
+    add(game.player_health as u64);
+    add(game.armor as u64);
+    add(game.battery.to_bits());
+    add(game.invulnerable_time.to_bits());
+    add(game.invulnerable_source.map_or(0, |kind| kind as u64 + 1));
+    add(game.level_time.to_bits());
+    add(game.player.inventory.keys.len() as u64);
+    for item in &game.items {
+        add(item.collected as u64);
+    }
+    for enemy in &game.enemies {
+        add(enemy.x.to_bits());
+        add(enemy.y.to_bits());
+    }
+    hash
+}
@@ -0,0 +1,378 @@
+// Progress kept between runs: which levels are unlocked and completed, best times, and the
+// level the player left halfway through. Stored as `name = value` lines after a version line:
+//
+//   version = 2
+//   level.Beginner Maze = unlocked completed best 42.35
+//   endless_best_depth = 3
+//   difficulty = hard               picked on the level select screen
+//   continue.level = Stone Fortress
+//   continue.difficulty = normal    the level keeps the difficulty it was started on
+//   continue.player = 3.5 10.5 1.57
+//
+// Files from older versions are upgraded through MIGRATIONS when loaded.
+use crate::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const SAVE_FILE: &str = "save.cfg";
+const SAVE_VERSION: u32 = 2;
+
+// MIGRATIONS[n] turns the entries of version n + 1 into those of version n + 2. A format change
+// bumps SAVE_VERSION and appends the step that upgrades the previous version.
+const MIGRATIONS: [fn(Vec<ConfigEntry>) -> Vec<ConfigEntry>; SAVE_VERSION as usize - 1] = [
+    add_snapshot_battery,
+];
+
+// Version 2 added the flashlight battery. A level left before then had a full one.
+fn add_snapshot_battery(mut entries: Vec<ConfigEntry>) -> Vec<ConfigEntry> {
+    if let Some(level) = entries.iter().find(|entry| entry.name == "continue.level") {
+        let line = level.line;
+        entries.push(ConfigEntry { line, name: String::from("continue.battery"), value: MAX_BATTERY.to_string() });
+    }
+    entries
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct LevelRecord {
+    pub unlocked: bool,
+    pub completed: bool,
+    pub best_time: Option<f64>, // Seconds
+}
+
+// A level left in progress. Enemies are not kept and start again from their posts.
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct Snapshot {
+    pub level: String, // Level name
+    pub x: f64,
+    pub y: f64,
+    pub angle: f64,
+    pub health: i32,
+    pub armor: i32,
+    pub battery: f64,
+    pub time: f64,
+    pub damage_taken: i32,
+    pub difficulty: Difficulty,
+    pub keys: Vec<KeyColor>,
+    pub collected: Vec<usize>,              // Indices into the level's items
+    pub unlocked_doors: Vec<(usize, usize)>,
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct SaveData {
+    pub levels: BTreeMap<String, LevelRecord>, // By level name
+    pub endless_best_depth: u32, // Generated levels cleared in the best endless run
+    pub difficulty: Difficulty,  // For the next level started
+    pub snapshot: Option<Snapshot>,
+}
+
+impl SaveData {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(Path::new(SAVE_FILE))
+    }
+
+    // An unreadable save is moved to the first free save.cfg.bak, save.cfg.bak.1, ... so no earlier
+    // backup is lost, and play starts fresh. A save from a newer version of the game is left where
+    // it is, and save_to refuses to overwrite it.
+    pub fn load_from(path: &Path) -> Self {
+        match Self::read(path) {
+            Ok(data) => data,
+            Err(_) if newer_version(path).is_some() => {
+                eprintln!("Warning: {} was written by a newer version of the game, starting without saved progress (the file is left untouched)", path.display());
+                Self::new()
+            },
+            Err(message) => {
+                let backup = backup_path(path);
+                eprintln!("Warning: {}: {}, starting without saved progress (old file kept as {})", path.display(), message, backup.display());
+                if let Err(e) = fs::rename(path, &backup) {
+                    eprintln!("Warning: could not move {} aside: {}", path.display(), e);
+                }
+                Self::new()
+            }
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self, String> {
+        Self::from_entries(read_config(path).map_err(|e| e.to_string())?)
+    }
+
+    // Also used for the progress a replay starts from
+    pub fn from_entries(mut entries: Vec<ConfigEntry>) -> Result<Self, String> {
+        if entries.is_empty() {
+            return Ok(Self::new());
+        }
+
+        let version = match entries.first() {
+            Some(entry) if entry.name == "version" => entry.value.parse::<u32>().map_err(|_| format!("invalid version `{}`", entry.value))?,
+            _ => return Err(String::from("missing version line")),
+        };
+        if version == 0 || version > SAVE_VERSION {
+            return Err(format!("unsupported version {} (this game writes version {})", version, SAVE_VERSION));
+        }
+        entries.remove(0);
+        for migrate in &MIGRATIONS[version as usize - 1..] {
+            entries = migrate(entries);
+        }
+
+        let mut data = Self::new();
+        let mut snapshot = Snapshot::default();
+        for entry in &entries {
+            data.read_entry(entry, &mut snapshot).map_err(|message| format!("line {}: {}", entry.line, message))?;
+        }
+        if !snapshot.level.is_empty() {
+            data.snapshot = Some(snapshot);
+        }
+        Ok(data)
+    }
+
+    fn read_entry(&mut self, entry: &ConfigEntry, snapshot: &mut Snapshot) -> Result<(), String> {
+        let value = entry.value.as_str();
+        if let Some(name) = entry.name.strip_prefix("level.") {
+            let record = self.levels.entry(name.to_string()).or_default();
+            let mut words = value.split_whitespace();
+            while let Some(word) = words.next() {
+                match word {
+                    "unlocked" => record.unlocked = true,
+                    "completed" => record.completed = true,
+                    "best" => record.best_time = Some(parse_number(words.next().unwrap_or(""))?),
+                    _ => return Err(format!("unknown level status `{}`", word)),
+                }
+            }
+            return Ok(());
+        }
+
+        match entry.name.as_str() {
+            "endless_best_depth" => self.endless_best_depth = parse_number(value)?,
+            "difficulty" => self.difficulty = parse_difficulty(value)?,
+            "continue.level" => snapshot.level = value.to_string(),
+            "continue.player" => {
+                let numbers = parse_numbers::<f64>(value)?;
+                if numbers.len() != 3 {
+                    return Err(String::from("expected `x y angle`"));
+                }
+                snapshot.x = numbers[0];
+                snapshot.y = numbers[1];
+                snapshot.angle = numbers[2];
+            },
+            "continue.health" => snapshot.health = parse_number(value)?,
+            "continue.armor" => snapshot.armor = parse_number(value)?,
+            "continue.battery" => snapshot.battery = parse_number(value)?,
+            "continue.time" => snapshot.time = parse_number(value)?,
+            "continue.damage_taken" => snapshot.damage_taken = parse_number(value)?,
+            "continue.difficulty" => snapshot.difficulty = parse_difficulty(value)?,
+            "continue.keys" => {
+                snapshot.keys = value.split_whitespace()
+                    .map(|name| KeyColor::from_name(name).ok_or_else(|| format!("unknown key color `{}`", name)))
+                    .collect::<Result<_, _>>()?;
+            },
+            "continue.items" => snapshot.collected = parse_numbers(value)?,
+            "continue.unlocked_doors" => {
+                snapshot.unlocked_doors = value.split_whitespace().map(|cell| {
+                    let (x, y) = cell.split_once(',').ok_or_else(|| format!("expected `x,y`, found `{}`", cell))?;
+                    Ok((parse_number(x)?, parse_number(y)?))
+                }).collect::<Result<_, String>>()?;
+            },
+            _ => return Err(format!("unknown entry `{}`", entry.name)),
+        }
+        Ok(())
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to(Path::new(SAVE_FILE))
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        if let Some(version) = newer_version(path) {
+            return Err(io::Error::other(format!("it holds version {} progress from a newer version of the game", version)));
+        }
+        write_config(path, "Saved progress, written by the game.", &self.entries())
+    }
+
+    // The save file's lines, version first
+    pub fn entries(&self) -> Vec<(String, String)> {
+        let mut entries = vec![(String::from("version"), SAVE_VERSION.to_string())];
+        for (name, record) in &self.levels {
+            let mut status = Vec::new();
+            if record.unlocked {
+                status.push(String::from("unlocked"));
+            }
+            if record.completed {
+                status.push(String::from("completed"));
+            }
+            if let Some(time) = record.best_time {
+                status.push(format!("best {:.2}", time));
+            }
+            entries.push((format!("level.{}", name), status.join(" ")));
+        }
+        entries.push((String::from("endless_best_depth"), self.endless_best_depth.to_string()));
+        entries.push((String::from("difficulty"), self.difficulty.name().to_string()));
+
+        if let Some(snapshot) = &self.snapshot {
+            let join = |words: Vec<String>| words.join(" ");
+            entries.push((String::from("continue.level"), snapshot.level.clone()));
+            entries.push((String::from("continue.player"), format!("{} {} {}", snapshot.x, snapshot.y, snapshot.angle)));
+            entries.push((String::from("continue.health"), snapshot.health.to_string()));
+            entries.push((String::from("continue.armor"), snapshot.armor.to_string()));
+            entries.push((String::from("continue.battery"), format!("{:.2}", snapshot.battery)));
+            entries.push((String::from("continue.time"), format!("{:.2}", snapshot.time)));
+            entries.push((String::from("continue.damage_taken"), snapshot.damage_taken.to_string()));
+            entries.push((String::from("continue.difficulty"), snapshot.difficulty.name().to_string()));
+            entries.push((String::from("continue.keys"), join(snapshot.keys.iter().map(|key| key.name().to_string()).collect())));
+            entries.push((String::from("continue.items"), join(snapshot.collected.iter().map(|index| index.to_string()).collect())));
+            entries.push((String::from("continue.unlocked_doors"), join(snapshot.unlocked_doors.iter().map(|(x, y)| format!("{},{}", x, y)).collect())));
+        }
+
+        entries
+    }
+
+    pub fn record(&self, level: &str) -> LevelRecord {
+        self.levels.get(level).cloned().unwrap_or_default()
+    }
+
+    // The first level is always open, the others once the one before is finished
+    pub fn is_unlocked(&self, levels: &LevelCatalog, index: usize) -> bool {
+        index == 0 || levels.levels.get(index).is_some_and(|level| self.record(&level.name).unlocked)
+    }
+
+    // Returns true when the time beats the level's best
+    pub fn complete_level(&mut self, levels: &LevelCatalog, index: usize, time: f64) -> bool {
+        let level = match levels.levels.get(index) {
+            Some(level) => level,
+            None => return false,
+        };
+        let record = self.levels.entry(level.name.clone()).or_default();
+        record.unlocked = true;
+        record.completed = true;
+        let best = record.best_time.is_none_or(|best| time < best);
+        if best {
+            record.best_time = Some(time);
+        }
+
+        if let Some(next) = levels.levels.get(index + 1) {
+            self.levels.entry(next.name.clone()).or_default().unlocked = true;
+        }
+        best
+    }
+}
+
+// The version of a save file written by a newer game, which this one must not touch
+fn newer_version(path: &Path) -> Option<u32> {
+    let entries = read_config(path).ok()?;
+    let entry = entries.first().filter(|entry| entry.name == "version")?;
+    entry.value.parse().ok().filter(|&version| version > SAVE_VERSION)
+}
+
+// save.cfg.bak, or save.cfg.bak.1, .2, ... once that is taken
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".bak");
+    let mut backup = PathBuf::from(&name);
+    let mut number = 0;
+    while backup.exists() {
+        number += 1;
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", number));
+        backup = PathBuf::from(numbered);
+    }
+    backup
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.trim().parse().map_err(|_| format!("invalid number `{}`", text))
+}
+
+fn parse_numbers<T: std::str::FromStr>(text: &str) -> Result<Vec<T>, String> {
+    text.split_whitespace().map(parse_number).collect()
+}
+
+fn parse_difficulty(text: &str) -> Result<Difficulty, String> {
+    Difficulty::from_name(text.trim()).ok_or_else(|| format!("unknown difficulty `{}`", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An empty directory per test, so tests running in parallel never share files
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raycasting-save-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn progress_survives_a_round_trip() {
+        let mut data = SaveData::new();
+        data.levels.insert(String::from("Beginner Maze"), LevelRecord { unlocked: true, completed: true, best_time: Some(42.25) });
+        data.levels.insert(String::from("Stone Fortress"), LevelRecord { unlocked: true, ..LevelRecord::default() });
+        data.endless_best_depth = 3;
+        data.difficulty = Difficulty::Hard;
+        data.snapshot = Some(Snapshot {
+            level: String::from("Stone Fortress"),
+            x: 3.5,
+            y: 10.5,
+            angle: 1.5,
+            health: 60,
+            armor: 20,
+            battery: 37.5,
+            time: 12.75,
+            damage_taken: 40,
+            difficulty: Difficulty::Normal,
+            keys: vec![KeyColor::Blue, KeyColor::Red],
+            collected: vec![0, 2],
+            unlocked_doors: vec![(4, 7)],
+        });
+
+        let path = scratch_dir("round-trip").join(SAVE_FILE);
+        data.save_to(&path).unwrap();
+        assert_eq!(SaveData::load_from(&path), data);
+    }
+
+    #[test]
+    fn version_1_saves_get_a_full_battery() {
+        let path = scratch_dir("migration").join(SAVE_FILE);
+        fs::write(&path, "version = 1\nlevel.Beginner Maze = unlocked\ncontinue.level = Beginner Maze\ncontinue.player = 1.5 2.5 0\ncontinue.health = 80\n").unwrap();
+
+        let data = SaveData::load_from(&path);
+        assert!(data.record("Beginner Maze").unlocked);
+        let snapshot = data.snapshot.expect("snapshot dropped");
+        assert_eq!((snapshot.x, snapshot.y, snapshot.health), (1.5, 2.5, 80));
+        assert_eq!(snapshot.battery, MAX_BATTERY);
+
+        fs::write(&path, "version = 1\nendless_best_depth = 2\n").unwrap();
+        let data = SaveData::load_from(&path);
+        assert_eq!((data.endless_best_depth, data.snapshot), (2, None));
+    }
+
+    #[test]
+    fn unreadable_saves_never_overwrite_an_older_backup() {
+        let dir = scratch_dir("backup");
+        let path = dir.join(SAVE_FILE);
+        for attempt in ["version = 2\nlevel.A = flying\n", "version = two\n"] {
+            fs::write(&path, attempt).unwrap();
+            assert_eq!(SaveData::load_from(&path), SaveData::new());
+            assert!(!path.exists());
+        }
+        assert_eq!(fs::read_to_string(dir.join("save.cfg.bak")).unwrap(), "version = 2\nlevel.A = flying\n");
+        assert_eq!(fs::read_to_string(dir.join("save.cfg.bak.1")).unwrap(), "version = two\n");
+    }
+
+    #[test]
+    fn saves_from_a_newer_game_are_left_alone() {
+        let dir = scratch_dir("newer");
+        let path = dir.join(SAVE_FILE);
+        let newer = format!("version = {}\nlevel.A = unlocked\n", SAVE_VERSION + 1);
+        fs::write(&path, &newer).unwrap();
+
+        assert_eq!(SaveData::load_from(&path), SaveData::new());
+        assert!(SaveData::new().save_to(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+        assert!(!dir.join("save.cfg.bak").exists());
+    }
+}
@@ -0,0 +1,275 @@
+// Player settings, saved to settings.cfg, and the options menu that edits them
+use crate::*;
+use std::io;
+use std::path::Path;
+
+pub const SETTINGS_FILE: &str = "settings.cfg";
+
+// Radians of turn per pixel of mouse movement at sensitivity 1.0
+const BASE_MOUSE_SENSITIVITY: f64 = 0.00005;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Setting {
+    MouseSensitivity,
+    InvertMouse,
+    Fov,
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    ResolutionScale,
+    Fog,
+    DamageFlash,
+}
+
+pub const SETTINGS: [Setting; 9] = [
+    Setting::MouseSensitivity, Setting::InvertMouse, Setting::Fov, Setting::MusicVolume, Setting::SfxVolume,
+    Setting::Fullscreen, Setting::ResolutionScale, Setting::Fog, Setting::DamageFlash,
+];
+
+impl Setting {
+    // Name used in the settings file
+    pub fn name(self) -> &'static str {
+        match self {
+            Setting::MouseSensitivity => "mouse_sensitivity",
+            Setting::InvertMouse => "invert_mouse",
+            Setting::Fov => "fov",
+            Setting::MusicVolume => "music_volume",
+            Setting::SfxVolume => "sfx_volume",
+            Setting::Fullscreen => "fullscreen",
+            Setting::ResolutionScale => "resolution_scale",
+            Setting::Fog => "fog",
+            Setting::DamageFlash => "damage_flash",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Setting::MouseSensitivity => "Mouse sensitivity",
+            Setting::InvertMouse => "Invert mouse",
+            Setting::Fov => "Field of view",
+            Setting::MusicVolume => "Music volume",
+            Setting::SfxVolume => "Effects volume",
+            Setting::Fullscreen => "Fullscreen",
+            Setting::ResolutionScale => "Resolution scale",
+            Setting::Fog => "Fog",
+            Setting::DamageFlash => "Damage flash",
+        }
+    }
+
+    // Minimum, maximum and step of the numeric settings
+    fn range(self) -> Option<(f64, f64, f64)> {
+        match self {
+            Setting::MouseSensitivity => Some((0.1, 5.0, 0.1)),
+            Setting::Fov => Some((50.0, 110.0, 5.0)),
+            Setting::MusicVolume | Setting::SfxVolume => Some((0.0, 1.0, 0.1)),
+            Setting::ResolutionScale => Some((0.25, 1.0, 0.25)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Settings {
+    pub mouse_sensitivity: f64, // Multiplier on the base turn speed
+    pub invert_mouse: bool,
+    pub fov: f64,               // Degrees
+    pub music_volume: f64,      // 0.0 - 1.0
+    pub sfx_volume: f64,        // 0.0 - 1.0
+    pub fullscreen: bool,
+    pub resolution_scale: f64,  // Fraction of the window resolution the 3D view is rendered at
+    pub fog: bool,
+    pub damage_flash: bool,     // Red tint when hurt
+}
+
+impl Settings {
+    pub fn defaults() -> Self {
+        Self {
+            mouse_sensitivity: 1.0,
+            invert_mouse: false,
+            fov: 60.0,
+            music_volume: 1.0,
+            sfx_volume: 1.0,
+            fullscreen: false,
+            resolution_scale: 1.0,
+            fog: true,
+            damage_flash: true,
+        }
+    }
+
+    pub fn load() -> Self {
+        Self::load_from(Path::new(SETTINGS_FILE))
+    }
+
+    // Settings the file leaves out or gets wrong keep their defaults
+    pub fn load_from(path: &Path) -> Self {
+        let mut settings = Self::defaults();
+        let entries = match read_config(path) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Warning: could not read {}: {}", path.display(), e);
+                return settings;
+            }
+        };
+
+        for entry in entries {
+            let result = match SETTINGS.iter().find(|setting| setting.name() == entry.name) {
+                Some(&setting) => settings.set_from_text(setting, &entry.value),
+                None => Err(format!("unknown setting `{}`", entry.name)),
+            };
+            if let Err(message) = result {
+                eprintln!("Warning: {}:{}: {}", path.display(), entry.line, message);
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        self.save_to(Path::new(SETTINGS_FILE))
+    }
+
+    pub fn save_to(&self, path: &Path) -> io::Result<()> {
+        let entries: Vec<(String, String)> = SETTINGS.iter().map(|&setting| {
+            let value = match (self.number(setting), self.flag(setting)) {
+                (Some(number), _) => format!("{}", number),
+                (_, Some(flag)) => format!("{}", flag),
+                _ => String::new(),
+            };
+            (setting.name().to_string(), value)
+        }).collect();
+        write_config(path, "Game settings, also editable from the options menu.", &entries)
+    }
+
+    // Turns a mouse movement in pixels into a turn in radians
+    pub fn mouse_look(&self, delta_x: f64, delta_y: f64) -> (f64, f64) {
+        let scale = BASE_MOUSE_SENSITIVITY * self.mouse_sensitivity * if self.invert_mouse { -1.0 } else { 1.0 };
+        (delta_x * scale, delta_y * scale)
+    }
+
+    // How the menu shows a setting
+    pub fn value_text(&self, setting: Setting) -> String {
+        if let Some(flag) = self.flag(setting) {
+            return String::from(if flag { "On" } else { "Off" });
+        }
+        let value = self.number(setting).unwrap_or(0.0);
+        match setting {
+            Setting::MouseSensitivity => format!("{:.1}x", value),
+            Setting::Fov => format!("{:.0} deg", value),
+            _ => format!("{:.0}%", value * 100.0),
+        }
+    }
+
+    // Steps a numeric setting, keeping it in range, or toggles a flag
+    pub fn adjust(&mut self, setting: Setting, steps: i32) {
+        if let (Some((min, max, step)), Some(value)) = (setting.range(), self.number_mut(setting)) {
+            // Rounded to hundredths so repeated steps do not drift
+            let stepped = ((*value / step).round() + steps as f64) * step;
+            *value = ((stepped * 100.0).round() / 100.0).clamp(min, max);
+        } else if let Some(flag) = self.flag_mut(setting) {
+            *flag = !*flag;
+        }
+    }
+
+    pub fn reset(&mut self, setting: Setting) {
+        let defaults = Self::defaults();
+        if let (Some(value), Some(default)) = (self.number_mut(setting), defaults.number(setting)) {
+            *value = default;
+        } else if let (Some(flag), Some(default)) = (self.flag_mut(setting), defaults.flag(setting)) {
+            *flag = default;
+        }
+    }
+
+    fn set_from_text(&mut self, setting: Setting, text: &str) -> Result<(), String> {
+        if let (Some((min, max, _)), Some(value)) = (setting.range(), self.number_mut(setting)) {
+            let number: f64 = text.parse().map_err(|_| format!("invalid number `{}` for {}", text, setting.name()))?;
+            if !(min..=max).contains(&number) {
+                return Err(format!("{} must be between {} and {}", setting.name(), min, max));
+            }
+            *value = number;
+        } else if let Some(flag) = self.flag_mut(setting) {
+            *flag = text.parse().map_err(|_| format!("expected true or false for {}", setting.name()))?;
+        }
+        Ok(())
+    }
+
+    fn number(&self, setting: Setting) -> Option<f64> {
+        match setting {
+            Setting::MouseSensitivity => Some(self.mouse_sensitivity),
+            Setting::Fov => Some(self.fov),
+            Setting::MusicVolume => Some(self.music_volume),
+            Setting::SfxVolume => Some(self.sfx_volume),
+            Setting::ResolutionScale => Some(self.resolution_scale),
+            _ => None,
+        }
+    }
+
+    fn flag(&self, setting: Setting) -> Option<bool> {
+        match setting {
+            Setting::InvertMouse => Some(self.invert_mouse),
+            Setting::Fullscreen => Some(self.fullscreen),
+            Setting::Fog => Some(self.fog),
+            Setting::DamageFlash => Some(self.damage_flash),
+            _ => None,
+        }
+    }
+
+    fn number_mut(&mut self, setting: Setting) -> Option<&mut f64> {
+        match setting {
+            Setting::MouseSensitivity => Some(&mut self.mouse_sensitivity),
+            Setting::Fov => Some(&mut self.fov),
+            Setting::MusicVolume => Some(&mut self.music_volume),
+            Setting::SfxVolume => Some(&mut self.sfx_volume),
+            Setting::ResolutionScale => Some(&mut self.resolution_scale),
+            _ => None,
+        }
+    }
+
+    fn flag_mut(&mut self, setting: Setting) -> Option<&mut bool> {
+        match setting {
+            Setting::InvertMouse => Some(&mut self.invert_mouse),
+            Setting::Fullscreen => Some(&mut self.fullscreen),
+            Setting::Fog => Some(&mut self.fog),
+            Setting::DamageFlash => Some(&mut self.damage_flash),
+            _ => None,
+        }
+    }
+}
+
+pub struct OptionsMenu {
+    pub selected: usize, // Row in SETTINGS
+    pub error: Option<String>, // Last save failure
+}
+
+impl OptionsMenu {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            error: None,
+        }
+    }
+
+    // Returns false once the player leaves the menu. Changes apply right away and are saved
+    // when persist is set.
+    pub fn update(&mut self, input: &InputState, settings: &mut Settings, persist: bool) -> bool {
+        let setting = SETTINGS[self.selected];
+        let before = settings.clone();
+
+        if input.is_pressed(Action::Back) {
+            return false;
+        } else if input.is_pressed(Action::MenuUp) {
+            self.selected = (self.selected + SETTINGS.len() - 1) % SETTINGS.len();
+        } else if input.is_pressed(Action::MenuDown) {
+            self.selected = (self.selected + 1) % SETTINGS.len();
+        } else if input.is_pressed(Action::MenuLeft) {
+            settings.adjust(setting, -1);
+        } else if input.is_pressed(Action::MenuRight) || input.is_pressed(Action::MenuSelect) {
+            settings.adjust(setting, 1);
+        } else if input.is_pressed(Action::Clear) {
+            settings.reset(setting);
+        }
+
+        if persist && *settings != before {
+            self.error = settings.save().err().map(|e| format!("Could not save {}: {}", SETTINGS_FILE, e));
+        }
+        true
+    }
+}
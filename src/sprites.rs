@@ -0,0 +1,53 @@
+use crate::textures::*;
+
+#[derive(Clone)]
+pub struct Sprite {
+    pub x: f64,
+    pub y: f64,
+    pub frames: Vec<usize>, // Sprite texture ids, one per animation frame
+    pub frame_duration: f64, // Seconds per frame (ignored for static sprites)
+    pub scale: f64, // Height relative to a wall, sprites stand on the floor
+    pub animation_time: f64,
+}
+
+impl Sprite {
+    pub fn new(x: f64, y: f64, texture: usize) -> Self {
+        Self::new_animated(x, y, vec![texture], 0.0)
+    }
+
+    pub fn new_animated(x: f64, y: f64, frames: Vec<usize>, frame_duration: f64) -> Self {
+        Self {
+            x,
+            y,
+            frames,
+            frame_duration,
+            scale: 1.0,
+            animation_time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f64) {
+        if self.frames.len() > 1 {
+            self.animation_time += delta_time;
+        }
+    }
+
+    pub fn current_texture(&self) -> usize {
+        if self.frames.len() <= 1 || self.frame_duration <= 0.0 {
+            return self.frames[0];
+        }
+        let frame = (self.animation_time / self.frame_duration) as usize % self.frames.len();
+        self.frames[frame]
+    }
+}
+
+// Names used by level files
+pub fn sprite_by_name(name: &str, x: f64, y: f64) -> Option<Sprite> {
+    match name {
+        "pillar" => Some(Sprite::new(x, y, SPRITE_PILLAR)),
+        "barrel" => Some(Sprite::new(x, y, SPRITE_BARREL)),
+        "lamp" => Some(Sprite::new(x, y, SPRITE_LAMP)),
+        "torch" => Some(Sprite::new_animated(x, y, SPRITE_TORCH.to_vec(), 0.15)),
+        _ => None,
+    }
+}
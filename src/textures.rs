@@ -0,0 +1,511 @@
+use std::path::Path;
+
+pub const TEXTURE_SIZE: usize = 64;
+const TEXTURE_DIR: &str = "assets/textures";
+
+// Wall texture ids, chosen per wall type by the level palette
+pub const WALL_BRICK: usize = 0;
+pub const WALL_STONE: usize = 1;
+pub const WALL_WOOD: usize = 2;
+pub const WALL_METAL: usize = 3;
+pub const WALL_EXIT: usize = 4;
+pub const WALL_HAZARD: usize = 5;
+pub const WALL_DOOR: usize = 6;
+pub const WALL_DOOR_RED: usize = 7;
+pub const WALL_DOOR_BLUE: usize = 8;
+pub const WALL_DOOR_YELLOW: usize = 9;
+
+// Floor and ceiling texture ids stored per cell in Map
+pub const FLOOR_TILES: usize = 0;
+pub const FLOOR_CONCRETE: usize = 1;
+pub const FLOOR_PLANKS: usize = 2;
+pub const FLOOR_HAZARD: usize = 3;
+pub const CEILING_PANELS: usize = 4;
+pub const CEILING_ROCK: usize = 5;
+
+// Sprite texture ids
+pub const SPRITE_PILLAR: usize = 0;
+pub const SPRITE_BARREL: usize = 1;
+pub const SPRITE_LAMP: usize = 2;
+pub const SPRITE_TORCH: [usize; 3] = [3, 4, 5];
+pub const SPRITE_KEY_RED: usize = 6;
+pub const SPRITE_KEY_BLUE: usize = 7;
+pub const SPRITE_KEY_YELLOW: usize = 8;
+pub const SPRITE_GUARD_WALK: [usize; 2] = [9, 10];
+pub const SPRITE_GUARD_ATTACK: usize = 11;
+pub const SPRITE_MEDKIT: usize = 12;
+pub const SPRITE_ARMOR: usize = 13;
+pub const SPRITE_BATTERY: usize = 14;
+
+// Texels with less alpha than this are skipped when drawing sprites
+pub const ALPHA_THRESHOLD: u32 = 128;
+
+pub struct Texture {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>,
+}
+
+impl Texture {
+    pub fn from_file(path: &Path) -> Option<Self> {
+        let image = match image::open(path) {
+            Ok(image) => image.to_rgba8(),
+            Err(e) => {
+                println!("Warning: Could not load texture {}: {}", path.display(), e);
+                return None;
+            }
+        };
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = image
+            .pixels()
+            .map(|p| ((p[3] as u32) << 24) | ((p[0] as u32) << 16) | ((p[1] as u32) << 8) | p[2] as u32)
+            .collect();
+
+        Some(Self { width, height, pixels })
+    }
+
+    pub fn sample(&self, u: usize, v: usize) -> u32 {
+        self.pixels[(v % self.height) * self.width + (u % self.width)]
+    }
+
+    pub fn is_transparent(texel: u32) -> bool {
+        (texel >> 24) < ALPHA_THRESHOLD
+    }
+
+    // Procedural fallback used when the texture file is missing
+    fn generate(pattern: impl Fn(usize, usize) -> u32) -> Self {
+        Self::generate_with_alpha(|x, y| 0xFF000000 | pattern(x, y))
+    }
+
+    // Sprite patterns return ARGB so they can leave texels transparent
+    fn generate_with_alpha(pattern: impl Fn(usize, usize) -> u32) -> Self {
+        let mut pixels = Vec::with_capacity(TEXTURE_SIZE * TEXTURE_SIZE);
+        for y in 0..TEXTURE_SIZE {
+            for x in 0..TEXTURE_SIZE {
+                pixels.push(pattern(x, y));
+            }
+        }
+
+        Self {
+            width: TEXTURE_SIZE,
+            height: TEXTURE_SIZE,
+            pixels,
+        }
+    }
+}
+
+pub struct TextureManager {
+    wall_textures: Vec<Texture>,
+    surface_textures: Vec<Texture>,
+    sprite_textures: Vec<Texture>,
+}
+
+impl TextureManager {
+    pub fn new() -> Self {
+        // Indexed by the wall ids above
+        let wall_textures = vec![
+            Self::load("brick", brick_pattern),
+            Self::load("stone", stone_pattern),
+            Self::load("wood", wood_pattern),
+            Self::load("metal", metal_pattern),
+            Self::load("exit", exit_pattern),
+            Self::load("hazard", hazard_pattern),
+            Self::load("door", door_pattern),
+            Self::load("door_red", |x, y| locked_door_pattern(x, y, 0xFF3030)),
+            Self::load("door_blue", |x, y| locked_door_pattern(x, y, 0x3060FF)),
+            Self::load("door_yellow", |x, y| locked_door_pattern(x, y, 0xFFE030)),
+        ];
+
+        // Indexed by the surface ids above
+        let surface_textures = vec![
+            Self::load("floor_tiles", tiles_pattern),
+            Self::load("floor_concrete", concrete_pattern),
+            Self::load("floor_planks", planks_pattern),
+            Self::load("floor_hazard", hazard_floor_pattern),
+            Self::load("ceiling_panels", panels_pattern),
+            Self::load("ceiling_rock", rock_pattern),
+        ];
+
+        // Indexed by the sprite ids above
+        let sprite_textures = vec![
+            Self::load_sprite("sprite_pillar", pillar_sprite),
+            Self::load_sprite("sprite_barrel", barrel_sprite),
+            Self::load_sprite("sprite_lamp", lamp_sprite),
+            Self::load_sprite("sprite_torch_0", |x, y| torch_sprite(x, y, 0)),
+            Self::load_sprite("sprite_torch_1", |x, y| torch_sprite(x, y, 1)),
+            Self::load_sprite("sprite_torch_2", |x, y| torch_sprite(x, y, 2)),
+            Self::load_sprite("sprite_key_red", |x, y| key_sprite(x, y, 0xFF3030)),
+            Self::load_sprite("sprite_key_blue", |x, y| key_sprite(x, y, 0x3060FF)),
+            Self::load_sprite("sprite_key_yellow", |x, y| key_sprite(x, y, 0xFFE030)),
+            Self::load_sprite("sprite_guard_0", |x, y| guard_sprite(x, y, 0)),
+            Self::load_sprite("sprite_guard_1", |x, y| guard_sprite(x, y, 1)),
+            Self::load_sprite("sprite_guard_attack", |x, y| guard_sprite(x, y, 2)),
+            Self::load_sprite("sprite_medkit", medkit_sprite),
+            Self::load_sprite("sprite_armor", armor_sprite),
+            Self::load_sprite("sprite_battery", battery_sprite),
+        ];
+
+        Self { wall_textures, surface_textures, sprite_textures }
+    }
+
+    fn load(name: &str, fallback: impl Fn(usize, usize) -> u32) -> Texture {
+        Self::load_file(name).unwrap_or_else(|| Texture::generate(fallback))
+    }
+
+    fn load_sprite(name: &str, fallback: impl Fn(usize, usize) -> u32) -> Texture {
+        Self::load_file(name).unwrap_or_else(|| Texture::generate_with_alpha(fallback))
+    }
+
+    fn load_file(name: &str) -> Option<Texture> {
+        let path = Path::new(TEXTURE_DIR).join(format!("{}.png", name));
+        if path.exists() {
+            Texture::from_file(&path)
+        } else {
+            None
+        }
+    }
+
+    pub fn wall_texture(&self, id: usize) -> &Texture {
+        &self.wall_textures[id.min(self.wall_textures.len() - 1)]
+    }
+
+    pub fn surface_texture(&self, id: usize) -> &Texture {
+        &self.surface_textures[id.min(self.surface_textures.len() - 1)]
+    }
+
+    pub fn sprite_texture(&self, id: usize) -> &Texture {
+        &self.sprite_textures[id.min(self.sprite_textures.len() - 1)]
+    }
+}
+
+// Names used by level files
+pub fn wall_texture_by_name(name: &str) -> Option<usize> {
+    match name {
+        "brick" => Some(WALL_BRICK),
+        "stone" => Some(WALL_STONE),
+        "wood" => Some(WALL_WOOD),
+        "metal" => Some(WALL_METAL),
+        "exit" => Some(WALL_EXIT),
+        "hazard" => Some(WALL_HAZARD),
+        "door" => Some(WALL_DOOR),
+        _ => None,
+    }
+}
+
+pub fn surface_texture_by_name(name: &str) -> Option<usize> {
+    match name {
+        "tiles" => Some(FLOOR_TILES),
+        "concrete" => Some(FLOOR_CONCRETE),
+        "planks" => Some(FLOOR_PLANKS),
+        "hazard" => Some(FLOOR_HAZARD),
+        "panels" => Some(CEILING_PANELS),
+        "rock" => Some(CEILING_ROCK),
+        _ => None,
+    }
+}
+
+// Cheap deterministic per-texel noise in 0..=255
+fn noise(x: usize, y: usize, seed: u32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(374761393) ^ (y as u32).wrapping_mul(668265263) ^ seed.wrapping_mul(2246822519);
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    (h ^ (h >> 16)) & 0xFF
+}
+
+fn shade(color: u32, factor: f64) -> u32 {
+    let r = (((color >> 16) & 0xFF) as f64 * factor).min(255.0) as u32;
+    let g = (((color >> 8) & 0xFF) as f64 * factor).min(255.0) as u32;
+    let b = ((color & 0xFF) as f64 * factor).min(255.0) as u32;
+    (r << 16) | (g << 8) | b
+}
+
+fn brick_pattern(x: usize, y: usize) -> u32 {
+    let row = y / 16;
+    let offset = if row.is_multiple_of(2) { 0 } else { 16 };
+    let mortar = y % 16 < 2 || (x + offset) % 32 < 2;
+    if mortar {
+        return 0xB0A898;
+    }
+    shade(0xC03C30, 0.8 + noise(x, y, 1) as f64 / 255.0 * 0.3)
+}
+
+fn stone_pattern(x: usize, y: usize) -> u32 {
+    let block = noise(x / 16, y / 16, 2) as f64 / 255.0;
+    let edge = x.is_multiple_of(16) || y.is_multiple_of(16);
+    if edge {
+        return 0x20245A;
+    }
+    shade(0x4A50C8, 0.7 + block * 0.2 + noise(x, y, 3) as f64 / 255.0 * 0.15)
+}
+
+fn wood_pattern(x: usize, y: usize) -> u32 {
+    let plank = x / 16;
+    let grain = ((y as f64 * 0.35 + plank as f64 * 7.0).sin() * 0.5 + 0.5) * 0.2;
+    let edge = x.is_multiple_of(16);
+    if edge {
+        return 0x1E5A1E;
+    }
+    shade(0x3FBF4A, 0.7 + grain + noise(plank, y / 4, 4) as f64 / 255.0 * 0.1)
+}
+
+fn metal_pattern(x: usize, y: usize) -> u32 {
+    let panel_edge = x.is_multiple_of(32) || y.is_multiple_of(32);
+    let rivet = (x % 32 == 4 || x % 32 == 28) && (y % 32 == 4 || y % 32 == 28);
+    if panel_edge {
+        return 0x7A7420;
+    }
+    if rivet {
+        return 0xFFFFB0;
+    }
+    shade(0xE6DC40, 0.8 + noise(x, y / 8, 5) as f64 / 255.0 * 0.15)
+}
+
+fn exit_pattern(x: usize, y: usize) -> u32 {
+    let dx = x as i32 - 32;
+    let dy = y as i32 - 32;
+    let ring = ((dx * dx + dy * dy) as f64).sqrt() as usize / 6;
+    if ring.is_multiple_of(2) { 0xFF00FF } else { 0xA000C0 }
+}
+
+fn hazard_pattern(x: usize, y: usize) -> u32 {
+    if ((x + y) / 8).is_multiple_of(2) { 0xFF8800 } else { 0x222222 }
+}
+
+fn door_pattern(x: usize, y: usize) -> u32 {
+    let frame = !(3..61).contains(&x) || !(3..61).contains(&y);
+    if frame {
+        return 0x3A2A1A;
+    }
+    let handle = (x == 52 || x == 53) && (28..36).contains(&y);
+    if handle {
+        return 0xD0C060;
+    }
+    let panel_edge = (x - 3).is_multiple_of(29) || y == 31 || y == 32;
+    if panel_edge {
+        return 0x5A3A20;
+    }
+    shade(0x8B5A2B, 0.8 + noise(x / 2, y, 14) as f64 / 255.0 * 0.2)
+}
+
+// Regular door with a coloured stripe showing which key opens it
+fn locked_door_pattern(x: usize, y: usize, color: u32) -> u32 {
+    let stripe = (8..14).contains(&y) || (50..56).contains(&y);
+    let inside_frame = (3..61).contains(&x);
+    if stripe && inside_frame {
+        return shade(color, 0.8 + noise(x, y, 15) as f64 / 255.0 * 0.2);
+    }
+    door_pattern(x, y)
+}
+
+fn tiles_pattern(x: usize, y: usize) -> u32 {
+    let grout = x % 32 < 1 || y % 32 < 1;
+    if grout {
+        return 0x3A3A3A;
+    }
+    let checker = (x / 32 + y / 32).is_multiple_of(2);
+    let base = if checker { 0x8C8C84 } else { 0x6E6E68 };
+    shade(base, 0.9 + noise(x, y, 6) as f64 / 255.0 * 0.15)
+}
+
+fn concrete_pattern(x: usize, y: usize) -> u32 {
+    let crack = noise(x / 2, y / 2, 7) > 250;
+    if crack {
+        return 0x303438;
+    }
+    shade(0x5A5E64, 0.85 + noise(x, y, 8) as f64 / 255.0 * 0.2)
+}
+
+fn planks_pattern(x: usize, y: usize) -> u32 {
+    let plank = y / 16;
+    let seam = y.is_multiple_of(16) || (x + plank * 23).is_multiple_of(64);
+    if seam {
+        return 0x2E1E10;
+    }
+    let grain = ((x as f64 * 0.3 + plank as f64 * 5.0).sin() * 0.5 + 0.5) * 0.15;
+    shade(0x7A5030, 0.8 + grain + noise(x / 4, y, 9) as f64 / 255.0 * 0.1)
+}
+
+fn hazard_floor_pattern(x: usize, y: usize) -> u32 {
+    let glow = ((x as f64 * 0.2).sin() + (y as f64 * 0.25).cos()) * 0.25 + 0.5;
+    shade(0xFF6A00, 0.6 + glow * 0.4 + noise(x, y, 10) as f64 / 255.0 * 0.1)
+}
+
+fn panels_pattern(x: usize, y: usize) -> u32 {
+    let edge = x.is_multiple_of(32) || y.is_multiple_of(32);
+    if edge {
+        return 0x505860;
+    }
+    let light = (x % 32 > 12 && x % 32 < 20) && (y % 32 > 12 && y % 32 < 20);
+    if light {
+        return 0xE8F0FF;
+    }
+    shade(0xA8B0B8, 0.9 + noise(x, y, 11) as f64 / 255.0 * 0.1)
+}
+
+fn rock_pattern(x: usize, y: usize) -> u32 {
+    let lump = noise(x / 8, y / 8, 12) as f64 / 255.0;
+    shade(0x4E4438, 0.7 + lump * 0.3 + noise(x, y, 13) as f64 / 255.0 * 0.1)
+}
+
+// Sprite patterns: 0 alpha outside the silhouette
+fn pillar_sprite(x: usize, y: usize) -> u32 {
+    let dx = (x as i32 - 32).abs();
+    let capital = !(6..58).contains(&y);
+    if (capital && dx < 16) || dx < 11 {
+        let fluting = if !capital && dx % 4 == 0 { 0.75 } else { 1.0 };
+        let round = 1.0 - dx as f64 / 24.0;
+        return 0xFF000000 | shade(0xD8D0C0, (0.6 + round * 0.5) * fluting);
+    }
+    0
+}
+
+fn barrel_sprite(x: usize, y: usize) -> u32 {
+    if y < 26 {
+        return 0;
+    }
+    let dx = (x as i32 - 32).abs();
+    let bulge = 14 + ((y as f64 - 26.0) / 38.0 * std::f64::consts::PI).sin() as i32 * 3;
+    if dx > bulge {
+        return 0;
+    }
+    let round = 1.0 - dx as f64 / 20.0;
+    let band = y == 30 || y == 31 || y == 58 || y == 59;
+    let base = if band { 0x6A6A70 } else { 0x8A4E22 };
+    0xFF000000 | shade(base, 0.5 + round * 0.6)
+}
+
+fn lamp_sprite(x: usize, y: usize) -> u32 {
+    let dx = (x as i32 - 32).abs();
+    let dy = y as i32 - 12;
+    if dx * dx + dy * dy < 64 {
+        return 0xFFFFF2A0;
+    }
+    if dx <= 1 && y >= 12 {
+        return 0xFF303030;
+    }
+    if y >= 60 && dx < 8 {
+        return 0xFF404040;
+    }
+    0
+}
+
+fn torch_sprite(x: usize, y: usize, frame: usize) -> u32 {
+    let dx = x as i32 - 32;
+    if dx.abs() <= 2 && y >= 30 {
+        return 0xFF5A3A1A;
+    }
+    // Flame flickers by shifting its tip sideways each frame
+    let sway = [0, 2, -2][frame % 3];
+    let flame_y = y as i32 - 14;
+    if (0..16).contains(&flame_y) {
+        let width = (flame_y + 1) / 3;
+        let centre = dx - sway * (16 - flame_y) / 16;
+        if centre.abs() <= width {
+            let core = centre.abs() * 2 < width;
+            return if core { 0xFFFFF080 } else { 0xFFFF8020 };
+        }
+    }
+    0
+}
+
+fn key_sprite(x: usize, y: usize, color: u32) -> u32 {
+    // Small key lying near the floor: ring on the left, shaft and teeth to the right
+    let dx = x as i32 - 22;
+    let dy = y as i32 - 52;
+    let ring = dx * dx + dy * dy;
+    if (9..=30).contains(&ring) {
+        return 0xFF000000 | shade(color, 1.0);
+    }
+    let shaft = (27..44).contains(&x) && (51..54).contains(&y);
+    let teeth = (x == 38 || x == 39 || x == 42 || x == 43) && (54..58).contains(&y);
+    if shaft || teeth {
+        return 0xFF000000 | shade(color, 0.85);
+    }
+    0
+}
+
+// Frames 0 and 1 swap the legs while walking, frame 2 raises the arms to strike
+fn guard_sprite(x: usize, y: usize, frame: usize) -> u32 {
+    let dx = x as i32 - 32;
+    let y = y as i32;
+    let attacking = frame == 2;
+
+    // Head with glowing eyes
+    let head_dy = y - 14;
+    if dx * dx + head_dy * head_dy <= 36 {
+        let eye = head_dy.abs() <= 1 && (dx == -3 || dx == -2 || dx == 2 || dx == 3);
+        if eye {
+            return if attacking { 0xFFFF2020 } else { 0xFFFFC040 };
+        }
+        return 0xFF000000 | shade(0x6A7078, 1.0 - dx.abs() as f64 / 20.0);
+    }
+    // Torso
+    if (21..42).contains(&y) && dx.abs() <= 8 {
+        let plate = y == 28 || y == 35;
+        return 0xFF000000 | shade(if plate { 0x3A3E44 } else { 0x5A6068 }, 1.0 - dx.abs() as f64 / 24.0);
+    }
+    // Arms hang at the sides, or reach forward over the head when striking
+    let arm_top = if attacking { 8 } else { 22 };
+    let arm_bottom = if attacking { 24 } else { 40 };
+    if (arm_top..arm_bottom).contains(&y) && (10..=13).contains(&dx.abs()) {
+        return 0xFF4A5058;
+    }
+    // Legs step forward and back on alternate frames
+    if (42..64).contains(&y) {
+        let stride = if frame == 1 { 2 } else { 0 };
+        let left = (-7 + stride..=-3 + stride).contains(&dx);
+        let right = (3 - stride..=7 - stride).contains(&dx);
+        if left || right {
+            return 0xFF3A3E44;
+        }
+    }
+    0
+}
+
+fn medkit_sprite(x: usize, y: usize) -> u32 {
+    // White case with a red cross and a dark handle on top
+    let (x, y) = (x as i32, y as i32);
+    let handle = (28..36).contains(&x) && (39..43).contains(&y) && !((30..34).contains(&x) && y > 39);
+    if handle {
+        return 0xFF404040;
+    }
+    if !(20..44).contains(&x) || !(43..62).contains(&y) {
+        return 0;
+    }
+    let cross = ((29..35).contains(&x) && (46..59).contains(&y)) || ((25..39).contains(&x) && (50..55).contains(&y));
+    if cross {
+        return 0xFFE02020;
+    }
+    let edge = x == 20 || x == 43 || y == 43 || y == 61;
+    0xFF000000 | shade(0xF0F0F0, if edge { 0.7 } else { 1.0 - (x - 32).abs() as f64 / 60.0 })
+}
+
+fn armor_sprite(x: usize, y: usize) -> u32 {
+    // Green vest: shoulders, a neck hole and a darker plate band
+    let dx = (x as i32 - 32).abs();
+    let y = y as i32;
+    if !(34..62).contains(&y) {
+        return 0;
+    }
+    let half_width = if y < 40 { 13 } else { 11 };
+    let neck = dx < 5 && y < 40;
+    if dx > half_width || neck {
+        return 0;
+    }
+    let plate = y == 48 || y == 49 || y == 55 || y == 56;
+    0xFF000000 | shade(if plate { 0x2A7A2A } else { 0x40C040 }, 1.0 - dx as f64 / 30.0)
+}
+
+fn battery_sprite(x: usize, y: usize) -> u32 {
+    // Upright cell: copper cap, black body with a yellow band
+    let dx = (x as i32 - 32).abs();
+    let y = y as i32;
+    if dx <= 2 && (38..41).contains(&y) {
+        return 0xFFC08040;
+    }
+    if dx > 6 || !(41..62).contains(&y) {
+        return 0;
+    }
+    let base = if (46..54).contains(&y) { 0xFFE030 } else { 0x303030 };
+    0xFF000000 | shade(base, 1.0 - dx as f64 / 14.0)
+}
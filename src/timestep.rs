@@ -0,0 +1,34 @@
+// Fixed-rate simulation clock. The game always advances in steps of TICK seconds however fast
+// frames come, so the same input sequence gives the same game state on every machine.
+pub const TICK_RATE: u32 = 60; // Ticks per second
+pub const TICK: f64 = 1.0 / TICK_RATE as f64;
+
+// After a longer stall the lost time is dropped instead of caught up in one burst
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+pub struct FixedTimestep {
+    accumulator: f64, // Wall-clock time not yet simulated
+}
+
+impl FixedTimestep {
+    pub fn new() -> Self {
+        Self { accumulator: 0.0 }
+    }
+
+    // Adds a frame's wall-clock time and returns how many ticks to run for it
+    pub fn advance(&mut self, frame_time: f64) -> u32 {
+        self.accumulator += frame_time.max(0.0);
+        let ticks = (self.accumulator / TICK) as u32;
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.accumulator = 0.0;
+            return MAX_TICKS_PER_FRAME;
+        }
+        self.accumulator -= ticks as f64 * TICK;
+        ticks
+    }
+
+    // How far into the next tick the frame is, 0.0 - 1.0, for interpolating the view
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / TICK).clamp(0.0, 1.0)
+    }
+}
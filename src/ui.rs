@@ -1,22 +1,80 @@
 use crate::*;
 use std::time::Instant;
 
+const MESSAGE_TIME: f64 = 2.5; // Seconds a HUD message stays visible
+
 pub struct UI {
     pub start_time: Instant,
+    pub level_error: Option<String>, // Shown on the level select screen when a level fails to load
+    pub message: Option<(String, f64)>, // HUD message and the seconds it stays on screen
     pub animated_sprite_frame: usize,
     pub animation_timer: f64,
 }
 
+// The finished level as the success screen shows it
+pub struct SuccessView<'a> {
+    pub result: &'a LevelResult,
+    pub table: &'a [ScoreEntry], // The level's best scores, empty for generated levels
+    pub name_entry: Option<&'a NameEntry>, // Set while a new high score waits for a name
+}
+
+// The player's state shown on the HUD
+pub struct HudStats<'a> {
+    pub health: i32,
+    pub max_health: i32, // Starting health of the difficulty, fills the health bar
+    pub armor: i32,
+    pub battery: f64, // Flashlight charge, 0 - MAX_BATTERY
+    pub inventory: &'a Inventory,
+}
+
+// What the minimap draws on top of the level
+pub struct MinimapView<'a> {
+    pub player: &'a Player,
+    pub map: &'a Map,
+    pub items: &'a [Item], // Collected ones are skipped
+    pub enemies: &'a [Enemy],
+}
+
 impl UI {
     pub fn new() -> Self {
         Self {
             start_time: Instant::now(),
+            level_error: None,
+            message: None,
             animated_sprite_frame: 0,
             animation_timer: 0.0,
         }
     }
 
-    pub fn render_splash_screen(&mut self, buffer: &mut Vec<u32>, window_width: usize, window_height: usize) {
+    pub fn show_message(&mut self, text: &str) {
+        self.message = Some((text.to_string(), MESSAGE_TIME));
+    }
+
+    pub fn handle_event(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::ItemPicked(kind) => self.show_message(&match kind {
+                ItemKind::Key(color) => format!("Picked up the {} key", color.name()),
+                ItemKind::Medkit => String::from("Picked up a medkit"),
+                ItemKind::Armor => String::from("Picked up armor"),
+                ItemKind::Battery => String::from("Picked up a battery"),
+            }),
+            GameEvent::DoorLocked(color) => self.show_message(&format!("You need the {} key", color.name())),
+            GameEvent::FlashlightToggled { on } => self.show_message(if on { "Flashlight on" } else { "Flashlight off" }),
+            GameEvent::BatteryFlat => self.show_message("The flashlight battery is flat"),
+            _ => {},
+        }
+    }
+
+    pub fn update(&mut self, delta_time: f64) {
+        if let Some((_, time_left)) = &mut self.message {
+            *time_left -= delta_time;
+            if *time_left <= 0.0 {
+                self.message = None;
+            }
+        }
+    }
+
+    pub fn render_splash_screen(&mut self, buffer: &mut Vec<u32>, bindings: &KeyBindings, window_width: usize, window_height: usize) {
         // Clear with dark background
         for pixel in buffer.iter_mut() {
             *pixel = 0x001122;
@@ -32,13 +90,14 @@ impl UI {
         self.draw_text(buffer, "RUST EDITION", window_width / 2 - 60, window_height / 2 - 20, 0xFFFFFF, 2, window_width, window_height);
         
         // Instructions
-        self.draw_text(buffer, "Press SPACE to continue", window_width / 2 - 100, window_height / 2 + 40, 0xCCCCCC, 1, window_width, window_height);
+        let prompt = format!("Press {} to continue", bindings.label(Action::Confirm));
+        self.draw_text(buffer, &prompt, self.centered_x(&prompt, 1, window_width), window_height / 2 + 40, 0xCCCCCC, 1, window_width, window_height);
         
         // Draw bear illustration using circles
         self.draw_bear(buffer, window_width / 2, window_height / 2 + 120, elapsed, window_width, window_height);
     }
 
-    pub fn render_level_select(&self, buffer: &mut Vec<u32>, window_width: usize, window_height: usize) {
+    pub fn render_level_select(&self, buffer: &mut Vec<u32>, levels: &LevelCatalog, progress: &SaveData, bindings: &KeyBindings, window_width: usize, window_height: usize) {
         // Clear with gradient background
         for y in 0..window_height {
             for x in 0..window_width {
@@ -49,38 +108,121 @@ impl UI {
 
         // Title
         self.draw_text(buffer, "SELECT LEVEL", window_width / 2 - 70, 100, 0xFFFFFF, 3, window_width, window_height);
+
+        // Level left halfway through last time
+        if let Some(snapshot) = &progress.snapshot {
+            let text = format!("{} - Continue {} ({}, {})", bindings.label(Action::Confirm), snapshot.level, format_time(snapshot.time), snapshot.difficulty.label());
+            self.draw_text(buffer, &text, self.centered_x(&text, 1, window_width), 150, 0xFFFFFF, 1, window_width, window_height);
+        }
         
-        // Level options
-        self.draw_text(buffer, "1 - Beginner Maze", window_width / 2 - 80, 200, 0xFFFF44, 2, window_width, window_height);
-        self.draw_text(buffer, "2 - Stone Fortress", window_width / 2 - 90, 250, 0x44FFFF, 2, window_width, window_height);
-        self.draw_text(buffer, "3 - Metal Labyrinth", window_width / 2 - 95, 300, 0xFF44FF, 2, window_width, window_height);
+        // Level options, one per level key, with the progress made on each
+        let colors = [0xFFFF44, 0x44FFFF, 0xFF44FF];
+        let level_count = levels.len().min(LEVEL_SLOTS);
+        for (i, level) in levels.levels.iter().take(level_count).enumerate() {
+            let text = format!("{} - {}", bindings.label(Action::SelectLevel(i)), level.name);
+            let x = self.centered_x(&text, 2, window_width);
+            let y = 180 + i * 36;
+            let record = progress.record(&level.name);
+            let (color, status) = if !progress.is_unlocked(levels, i) {
+                (0x777777, String::from("LOCKED"))
+            } else if record.completed {
+                let best = record.best_time.map_or_else(String::new, |time| format!(" {}", format_time(time)));
+                (colors[i % colors.len()], format!("DONE{}", best))
+            } else {
+                (colors[i % colors.len()], String::new())
+            };
+            self.draw_text(buffer, &text, x, y, color, 2, window_width, window_height);
+            self.draw_text(buffer, &status, x + text.len() * 16 + 16, y + 4, color, 1, window_width, window_height);
+        }
         
         // Instructions
-        self.draw_text(buffer, "Press 1, 2, or 3 to select", window_width / 2 - 120, 400, 0xCCCCCC, 1, window_width, window_height);
+        let controls = format!("{}: Controls | {}: Options", bindings.label(Action::Controls), bindings.label(Action::Options));
+        let instructions = match level_count {
+            0 => format!("No levels found | {}", controls),
+            _ => format!("Press the key next to a level to select | {}", controls),
+        };
+        let endless = format!("{} - Endless (generated levels)", bindings.label(Action::Endless));
+        let endless_x = self.centered_x(&endless, 2, window_width);
+        self.draw_text(buffer, &endless, endless_x, 180 + level_count * 36, 0x44FF44, 2, window_width, window_height);
+        if progress.endless_best_depth > 0 {
+            let best = format!("BEST DEPTH {}", progress.endless_best_depth);
+            self.draw_text(buffer, &best, endless_x + endless.len() * 16 + 16, 184 + level_count * 36, 0x44FF44, 1, window_width, window_height);
+        }
+        
+        // Difficulty for the next level started
+        let difficulty = format!("{}/{} - Difficulty < {} >", bindings.label(Action::MenuLeft), bindings.label(Action::MenuRight), progress.difficulty.label());
+        self.draw_text(buffer, &difficulty, self.centered_x(&difficulty, 1, window_width), 214 + level_count * 36, 0xFFFFFF, 1, window_width, window_height);
+        
+        let instructions_y = 236 + level_count * 36;
+        self.draw_text(buffer, &instructions, self.centered_x(&instructions, 1, window_width), instructions_y, 0xCCCCCC, 1, window_width, window_height);
+        
+        // Last level loading error, if any
+        if let Some(error) = &self.level_error {
+            self.draw_text(buffer, error, self.centered_x(error, 1, window_width), instructions_y + 30, 0xFF4444, 1, window_width, window_height);
+        }
     }
 
-    pub fn render_success_screen(&self, buffer: &mut Vec<u32>, window_width: usize, window_height: usize) {
+    // table is the level's high scores on the difficulty played, empty for generated levels
+    pub fn render_success_screen(&self, buffer: &mut Vec<u32>, bindings: &KeyBindings, view: &SuccessView, window_width: usize, window_height: usize) {
+        let SuccessView { result, table, name_entry } = *view;
         // Clear with golden background
         for pixel in buffer.iter_mut() {
             *pixel = 0x332200;
         }
 
-        // Victory message
-        self.draw_text(buffer, "LEVEL COMPLETE!", window_width / 2 - 90, window_height / 2 - 60, 0xFFD700, 3, window_width, window_height);
-        self.draw_text(buffer, "Congratulations!", window_width / 2 - 80, window_height / 2 - 20, 0xFFFFFF, 2, window_width, window_height);
-        
-        // Instructions
-        self.draw_text(buffer, "Press SPACE for level select", window_width / 2 - 120, window_height / 2 + 40, 0xCCCCCC, 1, window_width, window_height);
-        
-        // Draw victory stars
+        // Draw victory stars behind the title
         for i in 0..10 {
             let x = window_width / 2 + ((i as f64 * 0.628).cos() * 100.0) as usize;
-            let y = window_height / 2 + ((i as f64 * 0.628).sin() * 50.0) as usize;
+            let y = 90 + ((i as f64 * 0.628).sin() * 50.0) as usize;
             self.draw_star(buffer, x, y, 0xFFD700, window_width, window_height);
         }
+
+        // Victory message
+        self.draw_text(buffer, "LEVEL COMPLETE!", self.centered_x("LEVEL COMPLETE!", 3, window_width), 70, 0xFFD700, 3, window_width, window_height);
+        self.draw_text(buffer, "Congratulations!", self.centered_x("Congratulations!", 2, window_width), 110, 0xFFFFFF, 2, window_width, window_height);
+
+        // How the run went
+        let stats = format!("Time {}   Health {}   Damage taken {}", format_time(result.time), result.health.max(0), result.damage_taken);
+        self.draw_text(buffer, &stats, self.centered_x(&stats, 1, window_width), 160, 0xFFFFFF, 1, window_width, window_height);
+        let score = format!("SCORE {}", result.score);
+        self.draw_text(buffer, &score, self.centered_x(&score, 2, window_width), 180, 0xFFD700, 2, window_width, window_height);
+        let best_text = match result.best_time {
+            _ if result.new_best => String::from("New best time!"),
+            Some(best) => format!("Best time: {}", format_time(best)),
+            None => String::new(),
+        };
+        self.draw_text(buffer, &best_text, self.centered_x(&best_text, 1, window_width), 206, 0xFFD700, 1, window_width, window_height);
+
+        // Name for a new high score
+        if let Some(entry) = name_entry {
+            let title = "NEW HIGH SCORE! Type your name:";
+            self.draw_text(buffer, title, self.centered_x(title, 1, window_width), 236, 0x44FF44, 1, window_width, window_height);
+            let name = format!("{}_", entry.name);
+            self.draw_text(buffer, &name, self.centered_x(&name, 2, window_width), 252, 0xFFFFFF, 2, window_width, window_height);
+            let help = "ENTER: Save | BACKSPACE: Erase | ESC: Skip";
+            self.draw_text(buffer, help, self.centered_x(help, 1, window_width), 278, 0xCCCCCC, 1, window_width, window_height);
+        }
+
+        // The level's best scores, the new one highlighted
+        if !table.is_empty() {
+            let table_y = 310;
+            let title = format!("BEST SCORES - {}", result.difficulty.label());
+            self.draw_text(buffer, &title, self.centered_x(&title, 1, window_width), table_y, 0xFFD700, 1, window_width, window_height);
+            for (row, entry) in table.iter().enumerate() {
+                let text = format!("{}. {:<10} {:>6}  {}", row + 1, entry.name, entry.score, format_time(entry.time));
+                let color = if result.rank == Some(row) { 0x44FF44 } else { 0xFFFFFF };
+                self.draw_text(buffer, &text, self.centered_x(&text, 1, window_width), table_y + 18 + row * 14, color, 1, window_width, window_height);
+            }
+        }
+        
+        // Instructions
+        if name_entry.is_none() {
+            let prompt = format!("Press {} for level select", bindings.label(Action::Confirm));
+            self.draw_text(buffer, &prompt, self.centered_x(&prompt, 1, window_width), window_height.saturating_sub(60), 0xCCCCCC, 1, window_width, window_height);
+        }
     }
 
-    pub fn render_game_over_screen(&self, buffer: &mut Vec<u32>, window_width: usize, window_height: usize) {
+    pub fn render_game_over_screen(&self, buffer: &mut Vec<u32>, bindings: &KeyBindings, window_width: usize, window_height: usize) {
         // Clear with dark red background
         for pixel in buffer.iter_mut() {
             *pixel = 0x220000;
@@ -91,29 +233,138 @@ impl UI {
         self.draw_text(buffer, "You have died!", window_width / 2 - 70, window_height / 2 - 20, 0xFFFFFF, 2, window_width, window_height);
         
         // Instructions
-        self.draw_text(buffer, "Press R to restart", window_width / 2 - 80, window_height / 2 + 20, 0xCCCCCC, 1, window_width, window_height);
-        self.draw_text(buffer, "Press M for menu", window_width / 2 - 75, window_height / 2 + 50, 0xCCCCCC, 1, window_width, window_height);
+        let restart = format!("Press {} to restart", bindings.label(Action::Restart));
+        let menu = format!("Press {} for menu", bindings.label(Action::Menu));
+        self.draw_text(buffer, &restart, self.centered_x(&restart, 1, window_width), window_height / 2 + 20, 0xCCCCCC, 1, window_width, window_height);
+        self.draw_text(buffer, &menu, self.centered_x(&menu, 1, window_width), window_height / 2 + 50, 0xCCCCCC, 1, window_width, window_height);
+    }
+
+    // Drawn over the frozen level
+    pub fn render_pause_menu(&self, buffer: &mut Vec<u32>, selected: usize, window_width: usize, window_height: usize) {
+        for pixel in buffer.iter_mut() {
+            *pixel = self.interpolate_color(*pixel, 0x000000, 0.65);
+        }
+
+        let title = "PAUSED";
+        let top = (window_height / 2).saturating_sub(100);
+        self.draw_text(buffer, title, self.centered_x(title, 3, window_width), top, 0xFFFFFF, 3, window_width, window_height);
+
+        for (row, item) in PAUSE_ITEMS.iter().enumerate() {
+            let (text, color) = if row == selected {
+                (format!("> {} <", item.label()), 0xFFFF44)
+            } else {
+                (item.label().to_string(), 0xCCCCCC)
+            };
+            self.draw_text(buffer, &text, self.centered_x(&text, 2, window_width), top + 60 + row * 32, color, 2, window_width, window_height);
+        }
+
+        let help = "Up/Down: Select | Enter: Confirm | Esc: Resume";
+        self.draw_text(buffer, help, self.centered_x(help, 1, window_width), top + 70 + PAUSE_ITEMS.len() * 32, 0xCCCCCC, 1, window_width, window_height);
+    }
+
+    pub fn render_controls_menu(&self, buffer: &mut Vec<u32>, bindings: &KeyBindings, menu: &ControlsMenu, window_width: usize, window_height: usize) {
+        for pixel in buffer.iter_mut() {
+            *pixel = 0x101828;
+        }
+
+        let title = "CONTROLS";
+        self.draw_text(buffer, title, self.centered_x(title, 3, window_width), 30, 0xFFFFFF, 3, window_width, window_height);
+
+        // One row per action, one column per key
+        let label_x = (window_width / 2).saturating_sub(260);
+        let slot_x = |slot: usize| window_width / 2 + 20 + slot * 130;
+        let header_y = 80;
+        let row_height = 20;
+        self.draw_text(buffer, "Action", label_x, header_y, 0x8888AA, 1, window_width, window_height);
+        for slot in 0..KEYS_PER_ACTION {
+            self.draw_text(buffer, &format!("Key {}", slot + 1), slot_x(slot), header_y, 0x8888AA, 1, window_width, window_height);
+        }
+
+        for (row, action) in rebindable_actions().enumerate() {
+            let y = header_y + 20 + row * row_height;
+            let selected = row == menu.selected;
+            if selected {
+                for py in y.saturating_sub(4)..(y + 12).min(window_height) {
+                    for px in label_x.saturating_sub(8)..(slot_x(KEYS_PER_ACTION) - 10).min(window_width) {
+                        let in_slot = px + 8 >= slot_x(menu.slot) && px + 10 < slot_x(menu.slot + 1);
+                        buffer[py * window_width + px] = if in_slot { 0x4455AA } else { 0x2A3455 };
+                    }
+                }
+            }
+
+            let color = if bindings.has_conflict(action) {
+                0xFF4444
+            } else if selected {
+                0xFFFF44
+            } else {
+                0xFFFFFF
+            };
+            self.draw_text(buffer, &action.label(), label_x, y, color, 1, window_width, window_height);
+            for slot in 0..KEYS_PER_ACTION {
+                let text = if selected && menu.capturing && slot == menu.slot {
+                    String::from("...")
+                } else {
+                    bindings.key(action, slot).map_or_else(|| String::from("-"), key_label)
+                };
+                self.draw_text(buffer, &text, slot_x(slot), y, color, 1, window_width, window_height);
+            }
+        }
+
+        let help = "Up/Down: Action | Left/Right: Key | Enter: Rebind | Backspace: Clear | Esc: Back";
+        self.draw_text(buffer, help, self.centered_x(help, 1, window_width), window_height - 60, 0xCCCCCC, 1, window_width, window_height);
+        if let Some((text, is_error)) = &menu.status {
+            let color = if *is_error { 0xFF4444 } else { 0x44FF44 };
+            self.draw_text(buffer, text, self.centered_x(text, 1, window_width), window_height - 35, color, 1, window_width, window_height);
+        }
     }
 
-    pub fn render_minimap(&self, buffer: &mut Vec<u32>, player: &Player, map: &Map, window_width: usize, window_height: usize) {
+    pub fn render_options_menu(&self, buffer: &mut Vec<u32>, settings: &Settings, menu: &OptionsMenu, window_width: usize, window_height: usize) {
+        for pixel in buffer.iter_mut() {
+            *pixel = 0x101828;
+        }
+
+        let title = "OPTIONS";
+        self.draw_text(buffer, title, self.centered_x(title, 3, window_width), 60, 0xFFFFFF, 3, window_width, window_height);
+
+        let label_x = (window_width / 2).saturating_sub(220);
+        let value_x = window_width / 2 + 60;
+        for (row, &setting) in SETTINGS.iter().enumerate() {
+            let y = 150 + row * 30;
+            let selected = row == menu.selected;
+            if selected {
+                for py in y.saturating_sub(8)..(y + 16).min(window_height) {
+                    for px in label_x.saturating_sub(12)..(value_x + 170).min(window_width) {
+                        buffer[py * window_width + px] = 0x2A3455;
+                    }
+                }
+            }
+
+            let color = if selected { 0xFFFF44 } else { 0xFFFFFF };
+            let value = settings.value_text(setting);
+            let value = if selected { format!("< {} >", value) } else { format!("  {}", value) };
+            self.draw_text(buffer, setting.label(), label_x, y, color, 1, window_width, window_height);
+            self.draw_text(buffer, &value, value_x, y, color, 1, window_width, window_height);
+        }
+
+        let help = "Up/Down: Setting | Left/Right: Change | Backspace: Default | Esc: Back";
+        self.draw_text(buffer, help, self.centered_x(help, 1, window_width), window_height - 60, 0xCCCCCC, 1, window_width, window_height);
+        if let Some(error) = &menu.error {
+            self.draw_text(buffer, error, self.centered_x(error, 1, window_width), window_height - 35, 0xFF4444, 1, window_width, window_height);
+        }
+    }
+
+    pub fn render_minimap(&self, buffer: &mut Vec<u32>, view: &MinimapView, window_width: usize, window_height: usize) {
+        let MinimapView { player, map, items, enemies } = *view;
         let minimap_size = 120;
-        let minimap_x = window_width - minimap_size - 10;
+        let minimap_x = window_width.saturating_sub(minimap_size + 10);
         let minimap_y = 10;
         let cell_size = minimap_size / map.width.max(map.height);
         
         // Draw map cells with different colors for each wall type
         for y in 0..map.height {
             for x in 0..map.width {
-                let cell = map.get_cell(x, y);
-                let color = match cell {
-                    CellType::Empty => 0x000000,
-                    CellType::Wall1 => 0xFF4444, // Red walls
-                    CellType::Wall2 => 0x4444FF, // Blue walls
-                    CellType::Wall3 => 0x44FF44, // Green walls
-                    CellType::Wall4 => 0xFFFF44, // Yellow walls
-                    CellType::Exit => 0xFF00FF,   // Magenta exit
-                    CellType::Hazard => 0xFF8800, // Orange hazard
-                };
+                // Same colors as the level's wall palette
+                let color = map.get_cell_color(x, y);
                 
                 // Draw cell
                 for dy in 0..cell_size {
@@ -128,6 +379,26 @@ impl UI {
             }
         }
         
+        // Items still to pick up as small squares in their color
+        for item in items.iter().filter(|item| !item.collected) {
+            let item_x = minimap_x + (item.x() * cell_size as f64) as usize;
+            let item_y = minimap_y + (item.y() * cell_size as f64) as usize;
+            for py in item_y.saturating_sub(1)..=item_y + 1 {
+                for px in item_x.saturating_sub(1)..=item_x + 1 {
+                    if px < window_width && py < window_height {
+                        buffer[py * window_width + px] = item.kind.color();
+                    }
+                }
+            }
+        }
+        
+        // Enemies as red dots
+        for enemy in enemies {
+            let enemy_x = minimap_x as i32 + (enemy.x * cell_size as f64) as i32;
+            let enemy_y = minimap_y as i32 + (enemy.y * cell_size as f64) as i32;
+            self.draw_circle(buffer, enemy_x, enemy_y, 2, 0xFF2020, window_width, window_height);
+        }
+        
         // Draw player as arrow showing direction
         let player_x = minimap_x + (player.x * cell_size as f64) as usize;
         let player_y = minimap_y + (player.y * cell_size as f64) as usize;
@@ -158,18 +429,20 @@ impl UI {
         }
     }
 
-    pub fn render_hud(&mut self, buffer: &mut Vec<u32>, health: i32, window_width: usize, window_height: usize) {
+    pub fn render_hud(&mut self, buffer: &mut Vec<u32>, stats: &HudStats, bindings: &KeyBindings, window_width: usize, window_height: usize) {
+        let HudStats { health, max_health, armor, battery, inventory } = *stats;
         // Health bar
         let health_bar_width = 200;
         let health_bar_height = 20;
         let health_bar_x = 10;
-        let health_bar_y = window_height - 40;
+        let health_bar_y = window_height.saturating_sub(40);
         
         // Health bar rendering
-        let health_width = (health_bar_width as f64 * (health as f64 / 100.0)) as usize;
-        let health_color = if health > 60 {
+        let health_fraction = (health as f64 / max_health as f64).clamp(0.0, 1.0);
+        let health_width = (health_bar_width as f64 * health_fraction) as usize;
+        let health_color = if health_fraction > 0.6 {
             0x44FF44
-        } else if health > 30 {
+        } else if health_fraction > 0.3 {
             0xFFFF44
         } else {
             0xFF4444
@@ -197,10 +470,34 @@ impl UI {
         }
         
         // Health text
-        self.draw_text(buffer, &format!("Health: {}", health), health_bar_x, health_bar_y - 25, 0xFFFFFF, 1, window_width, window_height);
-        
+        let status = format!("Health: {}/{}   Armor: {}   Battery: {:.0}%", health, max_health, armor, battery / MAX_BATTERY * 100.0);
+        self.draw_text(buffer, &status, health_bar_x, health_bar_y.saturating_sub(25), 0xFFFFFF, 1, window_width, window_height);
+        
+        // Collected keys next to the health bar
+        for (i, key) in inventory.keys.iter().enumerate() {
+            let key_x = health_bar_x + health_bar_width + 15 + i * 22;
+            for y in health_bar_y + 4..health_bar_y + health_bar_height - 4 {
+                for x in key_x..key_x + 14 {
+                    if x < window_width && y < window_height {
+                        buffer[y * window_width + x] = key.color();
+                    }
+                }
+            }
+        }
+
+        // Message centered above the crosshair
+        if let Some((text, _)) = &self.message {
+            let x = self.centered_x(text, 2, window_width);
+            self.draw_text(buffer, text, x, (window_height / 2).saturating_sub(60), 0xFFFFFF, 2, window_width, window_height);
+        }
+
         // Controls help
-        self.draw_text(buffer, "WASD: Move | Mouse: Look | F: Flashlight", 10, 10, 0xCCCCCC, 1, window_width, window_height);
+        let movement: Vec<String> = [Action::MoveForward, Action::StrafeLeft, Action::MoveBackward, Action::StrafeRight]
+            .iter().map(|&action| bindings.label(action)).collect();
+        let separator = if movement.iter().all(|label| label.len() == 1) { "" } else { "/" };
+        let help = format!("{}: Move | Mouse: Look | {}: Use | {}: Flashlight",
+            movement.join(separator), bindings.label(Action::Use), bindings.label(Action::Flashlight));
+        self.draw_text(buffer, &help, 10, 10, 0xCCCCCC, 1, window_width, window_height);
         
         // Draw crosshair in center of screen
         self.draw_crosshair(buffer, window_width, window_height);
@@ -213,7 +510,7 @@ impl UI {
         }
         
         // Draw animated sprite in corner
-        self.render_animated_sprite(buffer, window_width - 50, window_height - 50, self.animation_timer * 10.0, window_width, window_height);
+        self.render_animated_sprite(buffer, window_width.saturating_sub(50), window_height.saturating_sub(50), self.animation_timer * 10.0, window_width, window_height);
     }
 
     fn render_animated_sprite(&self, buffer: &mut Vec<u32>, x: usize, y: usize, time: f64, window_width: usize, window_height: usize) {
@@ -242,6 +539,10 @@ impl UI {
         }
     }
 
+    fn centered_x(&self, text: &str, scale: usize, window_width: usize) -> usize {
+        (window_width / 2).saturating_sub(text.chars().count() * 4 * scale)
+    }
+
     fn draw_text(&self, buffer: &mut Vec<u32>, text: &str, x: usize, y: usize, color: u32, scale: usize, window_width: usize, window_height: usize) {
         let font = self.get_simple_font();
         let char_width = 8 * scale;
@@ -251,7 +552,8 @@ impl UI {
             let char_x = x + i * char_width;
             if char_x >= window_width { break; }
             
-            let char_data = font.get(&ch).unwrap_or(&[0u8; 8]);
+            // The font only has capitals
+            let char_data = font.get(&ch.to_ascii_uppercase()).unwrap_or(&[0u8; 8]);
             
             for row in 0..8 {
                 for col in 0..8 {
@@ -294,7 +596,7 @@ impl UI {
         let crosshair_color = 0xFFFFFF; // White crosshair
         
         // Draw horizontal line
-        for x in (center_x - crosshair_size)..(center_x + crosshair_size + 1) {
+        for x in center_x.saturating_sub(crosshair_size)..(center_x + crosshair_size + 1) {
             for thickness in 0..crosshair_thickness + 1 {
                 if x < window_width && (center_y + thickness) < window_height {
                     buffer[(center_y + thickness) * window_width + x] = crosshair_color;
@@ -306,7 +608,7 @@ impl UI {
         }
         
         // Draw vertical line
-        for y in (center_y - crosshair_size)..(center_y + crosshair_size + 1) {
+        for y in center_y.saturating_sub(crosshair_size)..(center_y + crosshair_size + 1) {
             for thickness in 0..crosshair_thickness + 1 {
                 if (center_x + thickness) < window_width && y < window_height {
                     buffer[y * window_width + (center_x + thickness)] = crosshair_color;
@@ -444,30 +746,64 @@ impl UI {
         font.insert('G', [0x3C, 0x66, 0x60, 0x6E, 0x66, 0x66, 0x3C, 0x00]);
         font.insert('H', [0x66, 0x66, 0x66, 0x7E, 0x66, 0x66, 0x66, 0x00]);
         font.insert('I', [0x3C, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, 0x00]);
+        font.insert('J', [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x6C, 0x38, 0x00]);
+        font.insert('K', [0x66, 0x6C, 0x78, 0x70, 0x78, 0x6C, 0x66, 0x00]);
         font.insert('L', [0x60, 0x60, 0x60, 0x60, 0x60, 0x60, 0x7E, 0x00]);
         font.insert('M', [0x63, 0x77, 0x7F, 0x6B, 0x63, 0x63, 0x63, 0x00]);
         font.insert('N', [0x66, 0x76, 0x7E, 0x7E, 0x6E, 0x66, 0x66, 0x00]);
         font.insert('O', [0x3C, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3C, 0x00]);
         font.insert('P', [0x7C, 0x66, 0x66, 0x7C, 0x60, 0x60, 0x60, 0x00]);
+        font.insert('Q', [0x3C, 0x66, 0x66, 0x66, 0x6E, 0x3C, 0x0E, 0x00]);
         font.insert('R', [0x7C, 0x66, 0x66, 0x7C, 0x6C, 0x66, 0x66, 0x00]);
         font.insert('S', [0x3C, 0x66, 0x60, 0x3C, 0x06, 0x66, 0x3C, 0x00]);
         font.insert('T', [0x7E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00]);
         font.insert('U', [0x66, 0x66, 0x66, 0x66, 0x66, 0x66, 0x3C, 0x00]);
         font.insert('V', [0x66, 0x66, 0x66, 0x66, 0x66, 0x3C, 0x18, 0x00]);
         font.insert('W', [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00]);
+        font.insert('X', [0x66, 0x66, 0x3C, 0x18, 0x3C, 0x66, 0x66, 0x00]);
         font.insert('Y', [0x66, 0x66, 0x66, 0x3C, 0x18, 0x18, 0x18, 0x00]);
+        font.insert('Z', [0x7E, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x7E, 0x00]);
         
         // Numbers
         font.insert('0', [0x3C, 0x66, 0x6E, 0x76, 0x66, 0x66, 0x3C, 0x00]);
         font.insert('1', [0x18, 0x38, 0x18, 0x18, 0x18, 0x18, 0x7E, 0x00]);
         font.insert('2', [0x3C, 0x66, 0x06, 0x0C, 0x30, 0x60, 0x7E, 0x00]);
         font.insert('3', [0x3C, 0x66, 0x06, 0x1C, 0x06, 0x66, 0x3C, 0x00]);
+        font.insert('4', [0x0C, 0x1C, 0x3C, 0x6C, 0x7E, 0x0C, 0x0C, 0x00]);
+        font.insert('5', [0x7E, 0x60, 0x7C, 0x06, 0x06, 0x66, 0x3C, 0x00]);
+        font.insert('6', [0x3C, 0x66, 0x60, 0x7C, 0x66, 0x66, 0x3C, 0x00]);
+        font.insert('7', [0x7E, 0x66, 0x0C, 0x18, 0x18, 0x18, 0x18, 0x00]);
+        font.insert('8', [0x3C, 0x66, 0x66, 0x3C, 0x66, 0x66, 0x3C, 0x00]);
+        font.insert('9', [0x3C, 0x66, 0x66, 0x3E, 0x06, 0x66, 0x3C, 0x00]);
         
         // Special characters
         font.insert('!', [0x18, 0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00]);
         font.insert(':', [0x00, 0x18, 0x18, 0x00, 0x18, 0x18, 0x00, 0x00]);
         font.insert('-', [0x00, 0x00, 0x00, 0x7E, 0x00, 0x00, 0x00, 0x00]);
+        font.insert('.', [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x00]);
+        font.insert(',', [0x00, 0x00, 0x00, 0x00, 0x00, 0x18, 0x18, 0x30]);
+        font.insert('\'', [0x18, 0x18, 0x30, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        font.insert('`', [0x18, 0x18, 0x0C, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        font.insert('/', [0x02, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00]);
+        font.insert('(', [0x0C, 0x18, 0x30, 0x30, 0x30, 0x18, 0x0C, 0x00]);
+        font.insert(')', [0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x18, 0x30, 0x00]);
+        font.insert('[', [0x3C, 0x30, 0x30, 0x30, 0x30, 0x30, 0x3C, 0x00]);
+        font.insert(']', [0x3C, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x3C, 0x00]);
+        font.insert('<', [0x0C, 0x18, 0x30, 0x60, 0x30, 0x18, 0x0C, 0x00]);
+        font.insert('>', [0x30, 0x18, 0x0C, 0x06, 0x0C, 0x18, 0x30, 0x00]);
+        font.insert('=', [0x00, 0x00, 0x7E, 0x00, 0x7E, 0x00, 0x00, 0x00]);
+        font.insert('+', [0x00, 0x18, 0x18, 0x7E, 0x18, 0x18, 0x00, 0x00]);
+        font.insert('?', [0x3C, 0x66, 0x06, 0x0C, 0x18, 0x00, 0x18, 0x00]);
+        font.insert('%', [0x62, 0x66, 0x0C, 0x18, 0x30, 0x66, 0x46, 0x00]);
+        font.insert('|', [0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x00]);
+        font.insert('_', [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF]);
         
         font
     }
-}
\ No newline at end of file
+}
+
+// Minutes, seconds and hundredths, like 1:05.42
+pub fn format_time(seconds: f64) -> String {
+    let hundredths = (seconds.max(0.0) * 100.0).round() as u64;
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}